@@ -1,15 +1,21 @@
 use anyhow::Result;
+use flate2::read::GzDecoder;
+use serde::Deserialize;
 use serde_json::Value;
 use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
+use tempfile::TempDir;
 use thiserror::Error;
 
 use crate::config::EBuilderConfig;
 use crate::environment::Platform;
-use crate::package::Package;
-use crate::utils::filesafe_package_name;
+use crate::package::{Package, PackageAuthor, PackageLicense, TasjeOptions};
+use crate::utils::{
+    self, current_year, filesafe_package_name, fill_copyright_template, transliterated_package_name,
+};
 
 #[derive(Error, Debug)]
 pub enum AppParseError {
@@ -23,8 +29,12 @@ pub enum AppParseError {
     Json5Error(#[from] json5::Error),
     #[error(transparent)]
     IoError(#[from] std::io::Error),
-    #[error("package.json holds no ebuilder config under `build` key. reading electron-builder.yml as fallback failed too: {0}")]
-    ConfigFallbackError(std::io::Error),
+    #[error("package.json holds no ebuilder config under `{0}` key. reading electron-builder.yml as fallback failed too: {1}")]
+    ConfigFallbackError(String, std::io::Error),
+    #[error("cannot override {0:?}, it is not a path to a config value")]
+    InvalidConfigOverride(String),
+    #[error("tarball holds no package/ directory, like the ones made by `npm pack` do")]
+    NoPackageInTarball,
     #[error("no file extension in provided config path")]
     NoConfigFileExtension,
     #[error("unknown file extension in config path: {0:?}")]
@@ -33,11 +43,28 @@ pub enum AppParseError {
     NodeProcessError { status_code: Option<i32>, stderr: Option<String> },
 }
 
+/// package.json and the config, read but not parsed yet
+#[derive(Debug, Clone)]
+pub struct AppValues {
+    pub package: Value,
+    pub config: Value,
+    /// `process.version` of node.js, if it was run to evaluate a js config
+    pub node_version: Option<String>,
+    /// keys defined more than once in a yaml or json config file, of which the last one is used
+    pub duplicate_keys: Vec<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct App {
     package: Package,
     config: EBuilderConfig,
     pub root: PathBuf,
+    transliterate_names: bool,
+    app_id_desktop_name: bool,
+    node_version: Option<String>,
+    duplicate_keys: Vec<String>,
+    /// removed once the last clone of the app is dropped
+    extracted_tarball: Option<Arc<TempDir>>,
 }
 
 impl App {
@@ -46,39 +73,117 @@ impl App {
             package,
             config,
             root,
+            transliterate_names: false,
+            app_id_desktop_name: false,
+            node_version: None,
+            duplicate_keys: Vec::new(),
+            extracted_tarball: None,
         }
     }
 
+    /// for configs that are already in memory, like ones built programmatically
+    pub fn from_values(package: Value, config: Value, root: PathBuf) -> Result<App, AppParseError> {
+        Ok(App::new(
+            Package::try_from(package)?,
+            serde_json::from_value(config)?,
+            root,
+        ))
+    }
+
+    /// like [App::from_values], but keeps the node.js version that evaluated the config
+    pub fn from_app_values(values: AppValues, root: PathBuf) -> Result<App, AppParseError> {
+        let mut app = App::from_values(values.package, values.config, root)?;
+        app.node_version = values.node_version;
+        app.duplicate_keys = values.duplicate_keys;
+        Ok(app)
+    }
+
     /// also looks for electron-builder.yml if there is no "build" in package.json
     pub fn new_from_package_file<P: AsRef<Path>>(package_file: P) -> Result<App, AppParseError> {
+        App::new_from_package_file_with_key(package_file, "build")
+    }
+
+    /// like [App::new_from_package_file], but the config is read from `config_key`
+    /// instead of "build", for projects keeping it under a custom key
+    pub fn new_from_package_file_with_key<P: AsRef<Path>>(
+        package_file: P,
+        config_key: &str,
+    ) -> Result<App, AppParseError> {
+        let package_file = package_file.as_ref();
+        App::from_app_values(
+            App::values_from_package_file_with_metadata(package_file, config_key)?,
+            package_file.parent().unwrap().to_path_buf(),
+        )
+    }
+
+    /// extracts an `npm pack` tarball (`.tgz`) to a temporary directory, and reads the app from it.
+    /// the directory is removed when the app is dropped, so the output dir should be outside of it
+    pub fn new_from_tarball<P: AsRef<Path>>(tarball: P) -> Result<App, AppParseError> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("tasje-")
+            .tempdir()?;
+        tar::Archive::new(GzDecoder::new(fs::File::open(tarball)?)).unpack(temp_dir.path())?;
+        // npm pack puts everything under package/
+        let package_file = temp_dir.path().join("package/package.json");
+        if !package_file.is_file() {
+            return Err(AppParseError::NoPackageInTarball);
+        }
+        let mut app = App::new_from_package_file(package_file)?;
+        app.extracted_tarball = Some(Arc::new(temp_dir));
+        Ok(app)
+    }
+
+    /// reads package.json and the config inside of it (or electron-builder.yml as a fallback)
+    /// without parsing them, so they can be modified before passing them to [App::from_values]
+    pub fn values_from_package_file<P: AsRef<Path>>(
+        package_file: P,
+        config_key: &str,
+    ) -> Result<(Value, Value), AppParseError> {
+        App::values_from_package_file_with_metadata(package_file, config_key)
+            .map(|values| (values.package, values.config))
+    }
+
+    /// like [App::values_from_package_file], but keeps what was found on reading the config,
+    /// for passing it to [App::from_app_values]
+    pub fn values_from_package_file_with_metadata<P: AsRef<Path>>(
+        package_file: P,
+        config_key: &str,
+    ) -> Result<AppValues, AppParseError> {
         let package_file = package_file.as_ref();
-        let package = Package::try_from(serde_json::from_str::<Value>(&fs::read_to_string(
-            package_file,
-        )?)?)?;
+        let package = serde_json::from_str::<Value>(&fs::read_to_string(package_file)?)?;
         let root = package_file.parent().unwrap();
-        let config = package
-            .value
-            .get("build")
-            .filter(|b| b.is_object())
-            .map(|b| -> Result<EBuilderConfig, AppParseError> {
-                Ok(serde_json::from_value(b.clone())?)
-            })
-            .unwrap_or_else(|| -> Result<EBuilderConfig, AppParseError> {
-                Ok(serde_yaml::from_reader(
-                    fs::File::open(root.join("electron-builder.yml"))
-                        .map_err(AppParseError::ConfigFallbackError)?,
-                )?)
-            })?;
-        Ok(App {
+        let mut duplicate_keys = Vec::new();
+        let config = match package.get(config_key).filter(|b| b.is_object()) {
+            Some(config) => config.clone(),
+            None => {
+                let yaml = fs::read_to_string(root.join("electron-builder.yml"))
+                    .map_err(|e| AppParseError::ConfigFallbackError(config_key.to_string(), e))?;
+                duplicate_keys = utils::duplicate_keys(serde_yaml::Deserializer::from_str(&yaml))?;
+                serde_yaml::from_str(&yaml)?
+            }
+        };
+        Ok(AppValues {
             package,
             config,
-            root: root.to_path_buf(),
+            node_version: None,
+            duplicate_keys,
         })
     }
 
-    /// `json_resolver` is a small script that has to console.log json
-    fn run_node_for_config(json_resolver: String) -> Result<EBuilderConfig, AppParseError> {
-        Ok(serde_json::from_slice(
+    /// `config_expr` is a promise of the config, which is printed as json
+    /// along with the node.js version, to record what evaluated the config
+    fn run_node_for_config(config_expr: String) -> Result<(Value, String), AppParseError> {
+        #[derive(Deserialize)]
+        struct NodeOutput {
+            version: String,
+            config: Value,
+        }
+
+        let json_resolver = format!(
+            "Promise.resolve({config_expr}).then((config) => \
+             console.log(JSON.stringify({{ version: process.version, config }})))"
+        );
+        let output: NodeOutput = serde_json::from_slice(
             &Command::new(std::env::var("NODE").unwrap_or_else(|_| "node".to_string()))
                 .arg("-e")
                 .arg(json_resolver)
@@ -97,7 +202,8 @@ impl App {
                     }
                 })??
                 .stdout,
-        )?)
+        )?;
+        Ok((output.config, output.version))
     }
 
     pub fn new_from_files<P1, P2>(package_file: P1, config_file: P2) -> Result<App, AppParseError>
@@ -106,26 +212,76 @@ impl App {
         P2: AsRef<Path>,
     {
         let package_file = package_file.as_ref();
-        let package = Package::try_from(serde_json::from_str::<Value>(&fs::read_to_string(
-            package_file,
-        )?)?)?;
+        App::from_app_values(
+            App::values_from_files_with_metadata(package_file, config_file)?,
+            package_file.parent().unwrap().to_path_buf(),
+        )
+    }
+
+    /// like [App::values_from_package_file], but with the config in a separate file
+    pub fn values_from_files<P1, P2>(
+        package_file: P1,
+        config_file: P2,
+    ) -> Result<(Value, Value), AppParseError>
+    where
+        P1: AsRef<Path>,
+        P2: AsRef<Path>,
+    {
+        App::values_from_files_with_metadata(package_file, config_file)
+            .map(|values| (values.package, values.config))
+    }
+
+    /// like [App::values_from_files], but keeps what was found on reading the config,
+    /// like the node.js version that evaluated a js config
+    pub fn values_from_files_with_metadata<P1, P2>(
+        package_file: P1,
+        config_file: P2,
+    ) -> Result<AppValues, AppParseError>
+    where
+        P1: AsRef<Path>,
+        P2: AsRef<Path>,
+    {
+        let package = serde_json::from_str::<Value>(&fs::read_to_string(package_file)?)?;
+        let mut node_version = None;
+        let mut run_node = |config_expr| -> Result<Value, AppParseError> {
+            let (config, version) = App::run_node_for_config(config_expr)?;
+            node_version = Some(version);
+            Ok(config)
+        };
+        let mut duplicate_keys = Vec::new();
         let config = match config_file
             .as_ref()
             .extension()
             .and_then(OsStr::to_str)
             .ok_or(AppParseError::NoConfigFileExtension)?
         {
-            "json" => serde_json::from_str(&fs::read_to_string(config_file.as_ref())?)?,
-            "yaml" | "yml" => serde_yaml::from_str(&fs::read_to_string(config_file.as_ref())?)?,
+            "json" => {
+                let json = fs::read_to_string(config_file.as_ref())?;
+                duplicate_keys =
+                    utils::duplicate_keys(&mut serde_json::Deserializer::from_str(&json))?;
+                serde_json::from_str(&json)?
+            }
+            "yaml" | "yml" => {
+                let yaml = fs::read_to_string(config_file.as_ref())?;
+                duplicate_keys = utils::duplicate_keys(serde_yaml::Deserializer::from_str(&yaml))?;
+                serde_yaml::from_str(&yaml)?
+            }
             "toml" => toml::from_str(&fs::read_to_string(config_file.as_ref())?)?,
             "json5" => json5::from_str(&fs::read_to_string(config_file.as_ref())?)?,
-            // runs node.js to import the file and serialize it to json, then parses the json output
-            "js" => App::run_node_for_config(format!(
-                "console.log(JSON.stringify(require({})))",
+            // runs node.js to import the file and serialize it to json, then parses the json output.
+            // in packages with "type": "module", .js files are ES modules, like .mjs
+            "js" if package.get("type").and_then(Value::as_str) == Some("module") => {
+                run_node(format!(
+                    "import({}).then((ebc) => ebc.default)",
+                    serde_json::to_string(&config_file.as_ref().canonicalize()?)?
+                ))?
+            }
+            "js" => run_node(format!(
+                "require({})",
                 serde_json::to_string(&config_file.as_ref().canonicalize()?)?
             ))?,
-            "mjs" => App::run_node_for_config(format!(
-                "import({}).then((ebc) => console.log(JSON.stringify(ebc.default)))",
+            "mjs" => run_node(format!(
+                "import({}).then((ebc) => ebc.default)",
                 serde_json::to_string(&config_file.as_ref().canonicalize()?)?
             ))?,
             unknown => {
@@ -134,16 +290,81 @@ impl App {
                 ))
             }
         };
-        Ok(App {
+        Ok(AppValues {
             package,
             config,
-            root: package_file.parent().unwrap().to_path_buf(),
+            node_version,
+            duplicate_keys,
         })
     }
 
+    /// sets a value in a config that was not parsed yet, like `directories.output` to `dist`.
+    /// the value is parsed as JSON if possible, otherwise it's used as a string
+    pub fn override_config_value(
+        config: &mut Value,
+        key: &str,
+        value: &str,
+    ) -> Result<(), AppParseError> {
+        let value =
+            serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+        let mut target = config;
+        for part in key.split('.') {
+            if part.is_empty() {
+                return Err(AppParseError::InvalidConfigOverride(key.to_string()));
+            }
+            if target.is_null() {
+                *target = Value::Object(Default::default());
+            }
+            target = target
+                .as_object_mut()
+                .ok_or_else(|| AppParseError::InvalidConfigOverride(key.to_string()))?
+                .entry(part)
+                .or_insert(Value::Null);
+        }
+        *target = value;
+        Ok(())
+    }
+
+    /// `process.version` of node.js, if it was run to evaluate a js config
+    pub fn node_version(&self) -> Option<&str> {
+        self.node_version.as_deref()
+    }
+
+    /// keys defined more than once in the config file, which might hide mistakes
+    pub fn duplicate_config_keys(&self) -> &[String] {
+        &self.duplicate_keys
+    }
+
     pub fn config(&self) -> &EBuilderConfig {
         &self.config
     }
+
+    /// the "tasje" key of package.json, applied on top of the config
+    pub fn tasje_options(&self) -> &TasjeOptions {
+        &self.package.manifest.tasje
+    }
+
+    /// converts non-ASCII names to ASCII when deriving file names from them,
+    /// instead of failing on them
+    pub fn transliterate_names(mut self, enabled: bool) -> Self {
+        self.transliterate_names = enabled;
+        self
+    }
+
+    /// names the desktop entry (and the metainfo) after "appId" if set,
+    /// instead of the package name, when there is no "desktopName"
+    pub fn app_id_desktop_name(mut self, enabled: bool) -> Self {
+        self.app_id_desktop_name = enabled;
+        self
+    }
+
+    fn filesafe_name(&self, name: &str) -> Result<String> {
+        if self.transliterate_names {
+            transliterated_package_name(name)
+        } else {
+            filesafe_package_name(name)
+        }
+    }
 }
 
 macro_rules! common_property {
@@ -165,7 +386,7 @@ impl<'a> App {
     }
 
     pub fn executable_name(&'a self, platform: Platform) -> Result<String> {
-        filesafe_package_name(
+        self.filesafe_name(
             common_property!(self, platform, executable_name)
                 .unwrap_or(&self.package.manifest.name),
         )
@@ -177,21 +398,29 @@ impl<'a> App {
             .as_str()
     }
 
+    /// file name of the desktop entry, which is also the base of the metainfo file name.
+    /// "desktopName" is used as it is, otherwise it's the package name made safe for file names,
+    /// or `<appId>.desktop` with [App::app_id_desktop_name]
     pub fn desktop_name(&'a self, platform: Platform) -> Result<String> {
-        common_property!(self, platform, desktop_name)
-            .map(String::clone)
-            .map(Result::Ok)
-            .unwrap_or_else(|| {
-                Ok(format!(
-                    "{}.desktop",
-                    filesafe_package_name(&self.package.manifest.name)?
-                ))
-            })
+        if let Some(name) = common_property!(self, platform, desktop_name) {
+            return Ok(name.clone());
+        }
+        if let Some(app_id) = self
+            .config
+            .app_id(platform)
+            .filter(|_| self.app_id_desktop_name)
+        {
+            return Ok(format!("{app_id}.desktop"));
+        }
+        Ok(format!(
+            "{}.desktop",
+            self.filesafe_name(&self.package.manifest.name)?
+        ))
     }
 
-    pub(crate) fn icon_locations(&'a self) -> Vec<PathBuf> {
+    pub(crate) fn icon_locations(&'a self, platform: Platform) -> Vec<PathBuf> {
         self.config
-            .icon_locations()
+            .icon_locations(platform)
             .into_iter()
             .map(|p| self.root.join(p))
             .collect()
@@ -203,8 +432,7 @@ impl<'a> App {
         if let Some(extra_metadata) = self
             .config
             .extra_metadata(platform)
-            .map(|m| m.as_object().cloned())
-            .flatten()
+            .and_then(|m| m.as_object().cloned())
         {
             for (k, v) in extra_metadata.into_iter() {
                 package.insert(k, v);
@@ -213,6 +441,66 @@ impl<'a> App {
         Ok(serde_json::to_vec(package)?)
     }
 
+    /// SPDX license expression of the app, from package.json
+    pub fn license(&'a self) -> Option<&'a str> {
+        self.package
+            .manifest
+            .license
+            .as_ref()
+            .map(PackageLicense::expression)
+    }
+
+    /// entry point of the app, taking extraMetadata into account.
+    /// like in node.js, defaults to index.js
+    pub fn main(&'a self, platform: Platform) -> &'a str {
+        self.config
+            .extra_metadata(platform)
+            .and_then(|m| m.get("main"))
+            .and_then(|m| m.as_str())
+            .or(self.package.manifest.main.as_deref())
+            .unwrap_or("index.js")
+    }
+
+    /// "copyright" from the config, with `${year}` and `${author}` filled in
+    pub fn copyright(&'a self, platform: Platform) -> Result<Option<String>> {
+        let Some(template) = self.config.copyright(platform) else {
+            return Ok(None);
+        };
+        Ok(Some(fill_copyright_template(
+            template,
+            current_year()?,
+            self.author().and_then(PackageAuthor::name),
+        )?))
+    }
+
+    /// "author" from package.json
+    pub fn author(&'a self) -> Option<&'a PackageAuthor> {
+        self.package.manifest.author.as_ref()
+    }
+
+    /// the marketing version, from package.json
+    pub fn version(&'a self) -> &'a str {
+        &self.package.manifest.version
+    }
+
+    /// the build number, like `CFBundleVersion` on macOS and `FileVersion` on windows.
+    /// defaults to the version from package.json
+    pub fn build_version(&'a self, platform: Platform) -> &'a str {
+        self.config
+            .build_version(platform)
+            .unwrap_or_else(|| self.version())
+    }
+
+    /// root of the files packed into the asar
+    pub fn app_root(&'a self, platform: Platform) -> PathBuf {
+        match self.config.app_dir(platform) {
+            Some(dir) => self.root.join(dir),
+            None => self.root.clone(),
+        }
+    }
+
+    /// like in electron-builder, relative to the project root,
+    /// even if the config file is somewhere else. absolute paths are used as they are
     pub fn output_dir(&'a self, platform: Platform) -> PathBuf {
         self.root.join(
             self.config
@@ -226,8 +514,12 @@ impl<'a> App {
 mod tests {
     use super::App;
     use crate::environment::Platform;
-    use crate::package::PackageManifest;
+    use crate::package::{Package, PackageManifest};
+    use crate::utils::current_year;
     use anyhow::Result;
+    use serde_json::json;
+    use std::fs;
+    use std::path::Path;
 
     static LINUX: Platform = Platform::Linux;
 
@@ -245,6 +537,207 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_desktop_name() -> Result<()> {
+        for (config, app_id_name, expected) in [
+            (
+                json!({
+                    "desktopName": "tasje-app.desktop",
+                    "appId": "rocks.selfisekai.Tasje",
+                }),
+                true,
+                "tasje-app.desktop",
+            ),
+            (
+                json!({
+                    "appId": "rocks.selfisekai.Tasje",
+                }),
+                true,
+                "rocks.selfisekai.Tasje.desktop",
+            ),
+            (
+                json!({
+                    "appId": "rocks.selfisekai.Tasje",
+                }),
+                false,
+                "electron_tasje.desktop",
+            ),
+            (json!({}), true, "electron_tasje.desktop"),
+        ] {
+            let app = App::from_values(
+                json!({
+                    "name": "electron_tasje",
+                    "version": "1.0.0",
+                }),
+                config,
+                "test_assets".into(),
+            )?
+            .app_id_desktop_name(app_id_name);
+            assert_eq!(app.desktop_name(LINUX)?, expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_version() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.2.0",
+            }),
+            json!({
+                "buildVersion": "1337",
+            }),
+            "test_assets".into(),
+        )?;
+        assert_eq!(app.version(), "1.2.0");
+        assert_eq!(app.build_version(LINUX), "1337");
+
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        assert_eq!(app.build_version(LINUX), app.version());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copyright() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "author": "Example <example@example.com>",
+            }),
+            json!({
+                "copyright": "© ${year} ${author}",
+            }),
+            "test_assets".into(),
+        )?;
+        assert_eq!(
+            app.copyright(LINUX)?,
+            Some(format!("© {} Example", current_year()?))
+        );
+
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "author": { "name": "Example" },
+            }),
+            json!({}),
+            "test_assets".into(),
+        )?;
+        assert_eq!(app.copyright(LINUX)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_keys() -> Result<()> {
+        let dir = std::env::current_dir()?.join(".test-workspace/app_duplicate_keys");
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(
+            dir.join("electron-builder.yml"),
+            "productName: Tasje\nlinux:\n  category: Development\n  category: Utility\nproductName: Tasje 2\n",
+        )?;
+        std::fs::write(
+            dir.join("electron-builder.json"),
+            r#"{"productName": "Tasje", "files": [{"from": "a"}, {"from": "b", "from": "c"}]}"#,
+        )?;
+
+        let app =
+            App::new_from_files("test_assets/package.json", dir.join("electron-builder.yml"))?;
+        assert_eq!(app.product_name(LINUX), "Tasje 2");
+        assert_eq!(
+            app.duplicate_config_keys(),
+            ["linux.category", "productName"]
+        );
+
+        let app = App::new_from_files(
+            "test_assets/package.json",
+            dir.join("electron-builder.json"),
+        )?;
+        assert_eq!(app.duplicate_config_keys(), ["files.1.from"]);
+
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        assert!(app.duplicate_config_keys().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_js_config() -> Result<()> {
+        let app = App::new_from_files(
+            "test_assets/package.json",
+            "test_assets/config/electron-builder.js",
+        )?;
+        assert_eq!(app.product_name(LINUX), "Tasje");
+        let version = app.node_version().unwrap();
+        let mut parts = version.strip_prefix('v').unwrap().split('.');
+        for _ in 0..3 {
+            assert!(parts.next().unwrap().parse::<u32>().is_ok());
+        }
+
+        let app = App::new_from_files(
+            "test_assets/package.json",
+            "test_assets/config/electron-builder.json",
+        )?;
+        assert_eq!(app.node_version(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_js_config_esm() -> Result<()> {
+        let root = std::env::current_dir()?.join(".test-workspace/app_js_config_esm");
+        fs::create_dir_all(&root)?;
+        fs::write(
+            root.join("package.json"),
+            r#"{"name": "tasje", "version": "1.0.0", "type": "module"}"#,
+        )?;
+        fs::write(
+            root.join("electron-builder.js"),
+            "export default { productName: \"Tasje ESM\" };\n",
+        )?;
+        let app = App::new_from_files(root.join("package.json"), root.join("electron-builder.js"))?;
+        assert_eq!(app.product_name(LINUX), "Tasje ESM");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_key() -> Result<()> {
+        let app = App::new_from_package_file_with_key(
+            "test_assets/package-config_key.json",
+            "electronBuilder",
+        )?;
+
+        assert_eq!(app.product_name(LINUX), "Tasje");
+        assert_eq!(app.executable_name(LINUX)?, "tasje");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_overrides() -> Result<()> {
+        let (package, mut config) =
+            App::values_from_package_file("test_assets/package.json", "build")?;
+        App::override_config_value(&mut config, "directories.output", "dist")?;
+        App::override_config_value(&mut config, "productName", "My App")?;
+        App::override_config_value(&mut config, "linux.category", r#"["Development"]"#)?;
+        assert!(App::override_config_value(&mut config, "productName.nested", "x").is_err());
+        assert!(App::override_config_value(&mut config, "linux..category", "x").is_err());
+        let app = App::from_values(package, config, "test_assets".into())?;
+
+        assert_eq!(app.product_name(LINUX), "My App");
+        assert_eq!(app.output_dir(LINUX), Path::new("test_assets/dist"));
+        assert_eq!(app.config().desktop_categories(LINUX), ["Development"]);
+        // untouched values stay
+        assert_eq!(app.executable_name(LINUX)?, "tasje");
+
+        Ok(())
+    }
+
     #[test]
     fn test_patched_package() -> Result<()> {
         let app = App::new_from_package_file("test_assets/package.json")?;
@@ -254,4 +747,45 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_transliterate_names() -> Result<()> {
+        let app = App::new(
+            Package::try_from(json!({
+                "name": "@corp/приложение",
+                "version": "1.0.0",
+            }))?,
+            serde_json::from_value(json!({}))?,
+            "test_assets".into(),
+        );
+        assert!(app.executable_name(LINUX).is_err());
+
+        let app = app.transliterate_names(true);
+        assert_eq!(app.executable_name(LINUX)?, "corp-prilozhenie");
+        assert_eq!(app.desktop_name(LINUX)?, "corp-prilozhenie.desktop");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_platform_executable_name() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "electron_tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "executableName": "tasje",
+                "win": {
+                    "executableName": "Tasje",
+                },
+            }),
+            "test_assets".into(),
+        )?;
+        assert_eq!(app.executable_name(LINUX)?, "tasje");
+        assert_eq!(app.executable_name(Platform::Windows)?, "Tasje");
+        assert_eq!(app.executable_name(Platform::Darwin)?, "tasje");
+
+        Ok(())
+    }
 }
@@ -1,15 +1,117 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{anyhow, Result};
+use clap::builder::PossibleValuesParser;
+use clap::{Parser, Subcommand, ValueEnum};
 use electron_tasje::app::App;
 use electron_tasje::config::CopyDef;
 use electron_tasje::desktop::DesktopGenerator;
 use electron_tasje::environment::{
     Architecture, Environment, Platform, HOST_ARCHITECTURE, HOST_PLATFORM,
 };
-use electron_tasje::pack::PackingProcessBuilder;
-use std::env::current_dir;
+use electron_tasje::flatpak::FlatpakGenerator;
+use electron_tasje::metainfo::MetainfoGenerator;
+use electron_tasje::pack::{
+    parse_asar_filelist, AsarLimits, FileListDiff, PackSummary, PackingProcessBuilder, PathRewrite,
+};
+use electron_tasje::utils::{default_icon_cache_dir, load_env_file};
+use regex::Regex;
+use std::env::{self, current_dir};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+
+/// options changing which files are packed into the asar, shared by "pack" and "diff"
+#[derive(clap::Args, Debug)]
+struct FileOptions {
+    #[clap(long, value_parser)]
+    /// additional globs to be interpreted as a part of "files" in ebuilder config.
+    /// unlike those, files matching these are not excluded by the built-in filters
+    additional_files: Vec<String>,
+
+    #[clap(long, value_parser)]
+    /// additional globs to be interpreted as a part of "asarUnpack" in ebuilder config
+    additional_asar_unpack: Vec<String>,
+
+    #[clap(long, value_parser)]
+    /// globs of files to pack even if excluded by the built-in filters (like *.md files)
+    reincluded_files: Vec<String>,
+
+    #[clap(long, value_parser)]
+    /// names of glob presets to add after "files" (minimal-node, include-sourcemaps)
+    preset: Vec<String>,
+
+    #[clap(long, action)]
+    /// pack the source maps (*.map files), which are left out by default
+    include_sourcemaps: bool,
+
+    #[clap(long, action)]
+    /// leave out hidden files and dirs, unless named explicitly in "files"
+    no_dotfiles: bool,
+
+    #[clap(long, value_parser)]
+    /// globs of files to leave out, applied after everything else (including node_modules)
+    exclude: Vec<String>,
+
+    #[clap(long, action)]
+    /// don't pack node_modules, only what's listed in "files" in ebuilder config
+    no_node_modules: bool,
+
+    #[clap(long, action, overrides_with = "no_follow_symlinks")]
+    /// pack the files that symlinks point to (default)
+    follow_symlinks: bool,
+
+    #[clap(long, action, overrides_with = "follow_symlinks")]
+    /// leave symlinks out of the package
+    no_follow_symlinks: bool,
+
+    #[clap(long, action)]
+    /// leave out files marked with export-ignore in the app's .gitattributes
+    export_ignore: bool,
+
+    #[clap(long, value_parser)]
+    /// rename paths in the asar, as regex=replacement, like
+    /// `^node_modules/@[^/]+/([^/]+)=node_modules/$1`. the first matching rule is applied
+    rewrite_path: Vec<String>,
+}
+
+impl FileOptions {
+    fn apply(self, builder: PackingProcessBuilder) -> Result<PackingProcessBuilder> {
+        let rewrites = self
+            .rewrite_path
+            .iter()
+            .map(|rule| {
+                // regexes are more likely to contain "=" than the replacements
+                let (pattern, replacement) = rule
+                    .rsplit_once('=')
+                    .ok_or_else(|| anyhow!("path rewrite has to be regex=replacement: {rule:?}"))?;
+                Ok(PathRewrite {
+                    pattern: Regex::new(pattern)?,
+                    replacement: replacement.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(builder
+            .with_node_modules(!self.no_node_modules)
+            .follow_symlinks(!self.no_follow_symlinks)
+            .additional_files(
+                self.additional_files
+                    .into_iter()
+                    .map(CopyDef::Simple)
+                    .collect(),
+            )
+            .additional_asar_unpack(self.additional_asar_unpack)
+            .reincluded_files(self.reincluded_files)
+            .excluded_files(self.exclude)
+            .presets(self.preset)
+            .include_sourcemaps(self.include_sourcemaps)
+            .dotfiles(!self.no_dotfiles)
+            .export_ignore(self.export_ignore)
+            .path_rewrites(rewrites))
+    }
+}
 
 #[derive(Subcommand, Debug)]
+// parsed once, so the size of the pack arguments does not matter
+#[allow(clippy::large_enum_variant)]
 #[clap(author, version, about, long_about = None)]
 enum Command {
     /// pack the resources
@@ -19,16 +121,106 @@ enum Command {
         output: Option<String>,
 
         #[clap(long, value_parser)]
-        /// additional globs to be interpreted as a part of "files" in ebuilder config
-        additional_files: Vec<String>,
+        /// directory to copy "extraFiles" to, relative to the output dir (default: the output dir)
+        extra_files_dir: Option<String>,
 
         #[clap(long, value_parser)]
-        /// additional globs to be interpreted as a part of "asarUnpack" in ebuilder config
-        additional_asar_unpack: Vec<String>,
+        /// directory to copy "extraResources" to, relative to the output dir (default: resources)
+        extra_resources_dir: Option<String>,
+
+        #[clap(long, value_parser)]
+        /// directory to copy the files unpacked from the asar to, relative to the output dir
+        /// (default: resources/app.asar.unpacked, the only place electron reads them from)
+        unpack_dir: Option<String>,
+
+        #[clap(flatten)]
+        files: FileOptions,
 
         #[clap(long, value_parser)]
         /// additional globs to be interpreted as a part of "extraResources" in ebuilder config
         additional_extra_resources: Vec<String>,
+
+        #[clap(long, action)]
+        /// don't extract the icons
+        no_icons: bool,
+
+        #[clap(long, action, conflicts_with = "asar_to_stdout")]
+        /// only generate the desktop entry, metainfo and icons,
+        /// without packing the asar or copying the extra files
+        metadata_only: bool,
+
+        #[clap(long, value_parser)]
+        /// directory to cache the optimized icons in (default: ~/.cache/tasje)
+        icon_cache_dir: Option<String>,
+
+        #[clap(long, action)]
+        /// optimize the icons from scratch, without caching them
+        no_icon_cache: bool,
+
+        #[clap(long, value_parser, num_args = 0..=1, default_missing_value = "0")]
+        /// unpack directories with native modules (*.node files) from the asar,
+        /// optionally with how many parent directories above them to include
+        unpack_native_modules: Option<usize>,
+
+        #[clap(long, value_parser)]
+        /// warn if more files than this are packed into the asar
+        max_asar_files: Option<usize>,
+
+        #[clap(long, value_parser)]
+        /// warn if a file packed into the asar is bigger than this many bytes (default: 2 GiB)
+        max_asar_file_size: Option<u64>,
+
+        #[clap(long, action)]
+        /// fail instead of warning when the asar limits are exceeded
+        strict_asar_limits: bool,
+
+        #[clap(long, action)]
+        /// go through the packing process without writing anything
+        dry_run: bool,
+
+        #[clap(long, action)]
+        /// fail if the "main" entry point from package.json is not packed
+        strict_main: bool,
+
+        #[clap(long, action)]
+        /// fail if the "from" directory of a file set does not exist,
+        /// or if a file set has neither "from" nor filters
+        strict_from: bool,
+
+        #[clap(long, action)]
+        /// fail if "extraMetadata" is not an object
+        strict_extra_metadata: bool,
+
+        #[clap(long, action)]
+        /// fail if a protocol or file association has no schemes or extensions
+        strict_associations: bool,
+
+        #[clap(long, action)]
+        /// also write app.asar.filelist, listing the packed files with their sizes
+        asar_filelist: bool,
+
+        #[clap(long, action)]
+        /// don't copy extra files again if they already exist with the same contents
+        no_overwrite_dest: bool,
+
+        #[clap(long, action)]
+        /// pack into a staging dir next to the output dir, replacing the output dir
+        /// only when done, so that it's never seen half-built
+        atomic_output: bool,
+
+        #[clap(long, value_parser = parse_size)]
+        /// how much of the packed files to read in parallel, like 512M or 1G (1024-based).
+        /// the asar is still assembled in memory as a whole
+        read_batch_size: Option<u64>,
+
+        #[clap(long, value_parser, num_args = 0..=1, default_missing_value = "20")]
+        /// print the biggest files packed into the asar, 20 unless given how many
+        print_files_size: Option<usize>,
+
+        #[clap(long, action, conflicts_with_all = ["dry_run", "asar_filelist"])]
+        /// write only the asar to stdout, for piping it into another tool.
+        /// nothing else is written, and only warnings are printed (to stderr)
+        asar_to_stdout: bool,
     },
     /// generate the desktop entry file (this is done as part of "tasje pack", too)
     GenerateDesktop {
@@ -36,32 +228,313 @@ enum Command {
         /// file or directory to put the generated entry in
         output: Option<String>,
     },
+    /// generate the AppStream metainfo file (this is done as part of "tasje pack", too)
+    GenerateMetainfo {
+        #[clap(short, long, value_parser)]
+        /// file or directory to put the generated metainfo in
+        output: Option<String>,
+    },
+    /// generate a flatpak manifest stub, as a starting point for packaging
+    GenerateFlatpak {
+        #[clap(short, long, value_parser)]
+        /// file or directory to put the generated manifest in
+        output: Option<String>,
+    },
+    /// list the files added, removed and resized in the asar since a previous build,
+    /// without packing anything
+    Diff {
+        #[clap(value_parser)]
+        /// app.asar.filelist of the previous build, written by "tasje pack --asar-filelist"
+        filelist: String,
+
+        #[clap(flatten)]
+        files: FileOptions,
+    },
 }
 
 use Command::*;
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ColorChoice {
+    /// if printing to a terminal, and NO_COLOR is not set
+    Auto,
+    Always,
+    Never,
+}
+
+/// adds ANSI colors to the output, if enabled
+#[derive(Debug, Clone, Copy)]
+struct Painter {
+    enabled: bool,
+}
+
+impl Painter {
+    /// https://no-color.org/ - any non-empty NO_COLOR disables colors, unless forced
+    fn new(choice: ColorChoice, no_color: Option<OsString>, terminal: bool) -> Self {
+        let enabled = match choice {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => terminal && no_color.is_none_or(|v| v.is_empty()),
+        };
+        Painter { enabled }
+    }
+
+    fn paint(&self, text: &str, code: &str) -> String {
+        if self.enabled {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn warning(&self, text: &str) -> String {
+        self.paint(text, "1;33")
+    }
+
+    fn success(&self, text: &str) -> String {
+        self.paint(text, "32")
+    }
+}
+
 #[derive(Parser, Debug)]
 struct Args {
     #[command(subcommand)]
     command: Command,
 
+    #[clap(long, value_parser)]
+    /// project directory with package.json, instead of the current one.
+    /// other paths are relative to it
+    root: Option<String>,
+
     #[clap(short, long, value_parser)]
     /// configuration file, if ebuilder configuration is outside package.json.
     /// can be YAML, TOML, JSON or JS
     config: Option<String>,
 
+    #[clap(long, value_parser, num_args = 0..=1, default_missing_value = ".env")]
+    /// load variables for ${env.*} templates from a .env file (default: .env in the project root).
+    /// variables set in the environment take precedence
+    env_file: Option<String>,
+
+    #[clap(long, value_parser, default_value = "build")]
+    /// key in package.json holding the ebuilder configuration
+    config_key: String,
+
+    #[clap(long, alias = "set", value_parser)]
+    /// override a config value, like `directories.output=dist`.
+    /// values are parsed as JSON if possible, otherwise used as strings
+    config_override: Vec<String>,
+
     #[clap(long, value_parser)]
+    /// build number, distinct from the version in package.json. overrides buildVersion
+    build_version: Option<String>,
+
+    #[clap(short, long, action = clap::ArgAction::Count)]
+    /// print what was packed, once for the summary, twice for every copied file
+    verbose: u8,
+
+    #[clap(short, long, action, conflicts_with = "verbose")]
+    /// print nothing but errors, not even the warnings
+    quiet: bool,
+
+    #[clap(long, value_enum, default_value_t = ColorChoice::Auto)]
+    /// when to color the output
+    color: ColorChoice,
+
+    #[clap(short, long, value_parser)]
+    /// how many threads to use at most, defaults to the number of cpus
+    jobs: Option<usize>,
+
+    #[clap(long, value_parser = PossibleValuesParser::new(Architecture::all().iter().map(Architecture::to_tasje)))]
     /// target cpu architecture (if cross-compiling, otherwise defaults to host)
     target_architecture: Option<String>,
 
-    #[clap(long, value_parser)]
+    #[clap(long, value_parser = PossibleValuesParser::new(Platform::all().iter().map(Platform::to_tasje)))]
     /// target platform/operating system (if cross-compiling, otherwise defaults to host)
     target_platform: Option<String>,
+
+    #[clap(long, action)]
+    /// convert non-ASCII package names to ASCII for executable and desktop entry names,
+    /// instead of failing
+    transliterate_names: bool,
+
+    #[clap(long, action)]
+    /// name the desktop entry and metainfo after "appId", if there is no "desktopName"
+    app_id_desktop_name: bool,
+
+    #[clap(long, action)]
+    /// add a desktop entry action for opening urls of every protocol scheme
+    protocol_actions: bool,
+
+    #[clap(long, value_parser)]
+    /// value for "Icon" in the desktop entry, like an absolute path.
+    /// defaults to the executable name, to be looked up in the icon theme
+    desktop_icon: Option<String>,
+
+    #[clap(long, action)]
+    /// end lines in the desktop entry with CRLF, instead of LF
+    desktop_crlf: bool,
+
+    #[clap(long, action)]
+    /// keep %U in "Exec" in the desktop entry, even if the app handles no urls or file types
+    desktop_always_field_code: bool,
+
+    #[clap(long, value_parser, num_args = 0..=1, default_missing_value = "CHANGELOG.md")]
+    /// list releases in the metainfo from a keep-a-changelog file (default: CHANGELOG.md)
+    metainfo_changelog: Option<String>,
+
+    #[clap(long, value_parser, default_value_t = 5)]
+    /// how many of the most recent releases from the changelog to list in the metainfo
+    metainfo_releases: usize,
+}
+
+/// a number of bytes, optionally with a K, M or G suffix, like `1GB` or `512M`
+fn parse_size(size: &str) -> Result<u64, String> {
+    let upper = size.trim().to_ascii_uppercase();
+    let number = upper
+        .strip_suffix("IB")
+        .or_else(|| upper.strip_suffix('B'))
+        .unwrap_or(&upper);
+    let (number, multiplier) = match number.chars().last() {
+        Some('K') => (&number[..number.len() - 1], 1 << 10),
+        Some('M') => (&number[..number.len() - 1], 1 << 20),
+        Some('G') => (&number[..number.len() - 1], 1 << 30),
+        _ => (number, 1),
+    };
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("not a size: {size:?}"))
+}
+
+/// like `1.5 MiB`, 1024-based like the sizes taken by --read-batch-size
+fn human_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "KiB", "MiB", "GiB"] {
+        if size < 1024.0 || unit == "GiB" {
+            return if unit == "B" {
+                format!("{bytes} B")
+            } else {
+                format!("{size:.1} {unit}")
+            };
+        }
+        size /= 1024.0;
+    }
+    unreachable!()
+}
+
+/// one line per changed file, then the counts
+fn print_diff<W: Write>(diff: &FileListDiff, mut out: W) -> Result<()> {
+    for (path, size) in &diff.added {
+        writeln!(out, "+ {path} ({})", human_size(*size))?;
+    }
+    for (path, size) in &diff.removed {
+        writeln!(out, "- {path} ({})", human_size(*size))?;
+    }
+    for (path, old_size, new_size) in &diff.resized {
+        writeln!(out, "~ {path} ({old_size} -> {new_size} B)")?;
+    }
+    writeln!(
+        out,
+        "{} added, {} removed, {} resized",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.resized.len()
+    )?;
+    Ok(())
+}
+
+fn packed_message(summary: &PackSummary, painter: Painter) -> String {
+    format!(
+        "{} {} files into the asar and copied {} extra files in {:?}",
+        painter.success("packed"),
+        summary.asar_files,
+        summary.extra_files - summary.skipped_extra_files,
+        summary.timings.total()
+    )
+}
+
+/// prints what happened when packing, depending on the verbosity
+struct Reporter {
+    verbose: u8,
+    quiet: bool,
+    /// how many of the biggest packed files to list
+    largest_files: Option<usize>,
+    stdout_painter: Painter,
+    stderr_painter: Painter,
+}
+
+impl Reporter {
+    fn warnings<W: Write>(&self, summary: &PackSummary, mut err: W) -> io::Result<()> {
+        if self.quiet {
+            return Ok(());
+        }
+        for warning in &summary.warnings {
+            writeln!(err, "{} {warning}", self.stderr_painter.warning("warning:"))?;
+        }
+        Ok(())
+    }
+
+    fn summary<W: Write>(
+        &self,
+        summary: &PackSummary,
+        dry_run: bool,
+        mut out: W,
+    ) -> io::Result<()> {
+        if self.quiet {
+            return Ok(());
+        }
+        if dry_run {
+            writeln!(
+                out,
+                "would pack {} files into the asar and copy {} extra files",
+                summary.asar_files, summary.extra_files
+            )?;
+        } else if self.verbose > 0 {
+            writeln!(out, "{}", packed_message(summary, self.stdout_painter))?;
+            if summary.skipped_extra_files > 0 {
+                writeln!(
+                    out,
+                    "skipped {} extra files, which were already in place",
+                    summary.skipped_extra_files
+                )?;
+            }
+        }
+        if self.verbose > 0 {
+            if let Some(version) = &summary.node_version {
+                writeln!(out, "config evaluated with node.js {version}")?;
+            }
+        }
+        if self.verbose > 1 {
+            let lists = &summary.copy_lists;
+            for (name, list) in [
+                ("asar", &lists.asar),
+                ("unpacked", &lists.unpacked),
+                ("extra", &lists.extra),
+            ] {
+                writeln!(out, "{name} copy list:")?;
+                for (source, dest) in list {
+                    writeln!(out, "  {} -> {}", source.display(), dest.display())?;
+                }
+            }
+        }
+        if let Some(count) = self.largest_files {
+            writeln!(out, "largest files in the asar:")?;
+            for (path, size) in summary.largest_asar_files(count)? {
+                writeln!(out, "  {:>10}  {}", human_size(size), path.display())?;
+            }
+        }
+        Ok(())
+    }
 }
 
 fn main() -> Result<()> {
-    let args = Args::parse();
+    run(Args::parse())
+}
 
+fn run(args: Args) -> Result<()> {
     let Args { config, .. } = args;
 
     let target_architecture = if let Some(arch) = args.target_architecture {
@@ -78,49 +551,363 @@ fn main() -> Result<()> {
         architecture: target_architecture,
         platform: target_platform,
     };
+    target_environment.check_supported()?;
+    if matches!(
+        args.command,
+        GenerateDesktop { .. } | GenerateMetainfo { .. } | GenerateFlatpak { .. }
+    ) && target_platform != Platform::Linux
+    {
+        eprintln!(
+            "warning: desktop entries, metainfo and flatpak manifests are meant for linux, generating them for a {target_platform} target anyway"
+        );
+    }
 
-    let root = current_dir()?;
+    let root = match &args.root {
+        Some(dir) => current_dir()?.join(dir),
+        None => current_dir()?,
+    };
+    if let Some(env_file) = &args.env_file {
+        load_env_file(root.join(env_file))?;
+    }
     let package_path = root.join("package.json");
-    let app = if let Some(config_path) = &config {
-        App::new_from_files(&package_path, root.join(config_path))?
+    let mut values = if let Some(config_path) = &config {
+        App::values_from_files_with_metadata(&package_path, root.join(config_path))?
     } else {
-        App::new_from_package_file(&package_path)?
+        App::values_from_package_file_with_metadata(&package_path, &args.config_key)?
     };
+    for assignment in &args.config_override {
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| anyhow!("config override has to be key=value: {assignment:?}"))?;
+        App::override_config_value(&mut values.config, key, value)?;
+    }
+    if let Some(build_version) = &args.build_version {
+        // quoted, so that a number like 1337 stays a string
+        App::override_config_value(
+            &mut values.config,
+            "buildVersion",
+            &serde_json::to_string(build_version)?,
+        )?;
+    }
+    let app = App::from_app_values(values, root.clone())?
+        .transliterate_names(args.transliterate_names)
+        .app_id_desktop_name(args.app_id_desktop_name);
+
+    let mut desktop_generator = DesktopGenerator::new()
+        .protocol_actions(args.protocol_actions)
+        .crlf(args.desktop_crlf)
+        .always_field_code(args.desktop_always_field_code);
+    if let Some(icon) = &args.desktop_icon {
+        desktop_generator = desktop_generator.icon(icon);
+    }
+
+    let mut metainfo_generator = MetainfoGenerator::new().max_releases(args.metainfo_releases);
+    if let Some(changelog) = &args.metainfo_changelog {
+        metainfo_generator = metainfo_generator.changelog(changelog);
+    }
 
     match args.command {
         Pack {
             output,
-            additional_files,
-            additional_asar_unpack,
+            extra_files_dir,
+            extra_resources_dir,
+            unpack_dir,
+            files,
             additional_extra_resources,
+            no_icons,
+            metadata_only,
+            icon_cache_dir,
+            no_icon_cache,
+            unpack_native_modules,
+            max_asar_files,
+            max_asar_file_size,
+            strict_asar_limits,
+            dry_run,
+            strict_main,
+            strict_from,
+            strict_extra_metadata,
+            strict_associations,
+            asar_filelist,
+            no_overwrite_dest,
+            atomic_output,
+            read_batch_size,
+            print_files_size,
+            asar_to_stdout,
         } => {
-            let mut builder =
-                PackingProcessBuilder::new(app).target_environment(target_environment);
+            let icon_cache = app.tasje_options().icon_cache.unwrap_or(true);
+            let mut builder = PackingProcessBuilder::new(app)
+                .target_environment(target_environment)
+                .desktop_generator(desktop_generator)
+                .metainfo_generator(metainfo_generator)
+                .with_icons(!no_icons)
+                .with_resources(!metadata_only);
             if let Some(out) = output {
                 builder = builder.base_output_dir(out);
             }
-            builder
-                .additional_files(
-                    additional_files
-                        .into_iter()
-                        .map(CopyDef::Simple)
-                        .collect(),
-                )
-                .additional_asar_unpack(additional_asar_unpack)
+            if let Some(dir) = extra_files_dir {
+                builder = builder.extra_files_output_dir(dir);
+            }
+            if let Some(dir) = extra_resources_dir {
+                builder = builder.extra_resources_output_dir(dir);
+            }
+            if let Some(dir) = unpack_dir {
+                builder = builder.unpack_output_dir(dir);
+            }
+            if !no_icon_cache {
+                if let Some(cache_dir) = icon_cache_dir
+                    .map(|dir| root.join(dir))
+                    .or_else(|| default_icon_cache_dir().filter(|_| icon_cache))
+                {
+                    builder = builder.icon_cache_dir(cache_dir);
+                }
+            }
+            if let Some(jobs) = args.jobs {
+                builder = builder.jobs(jobs);
+            }
+            if let Some(bytes) = read_batch_size {
+                builder = builder.read_batch_size(bytes);
+            }
+            if let Some(radius) = unpack_native_modules {
+                builder = builder.unpack_native_modules(radius);
+            }
+            let default_limits = AsarLimits::default();
+            builder = builder.asar_limits(AsarLimits {
+                max_files: max_asar_files.or(default_limits.max_files),
+                max_file_size: max_asar_file_size.or(default_limits.max_file_size),
+                strict: strict_asar_limits,
+            });
+            let process = files
+                .apply(builder)?
                 .additional_extra_resources(
                     additional_extra_resources
                         .into_iter()
                         .map(CopyDef::Simple)
                         .collect(),
                 )
-                .build()
-                .proceed()?;
+                .dry_run(dry_run)
+                .strict_main(strict_main)
+                .strict_from(strict_from)
+                .strict_extra_metadata(strict_extra_metadata)
+                .strict_associations(strict_associations)
+                .asar_filelist(asar_filelist)
+                .overwrite_dest(!no_overwrite_dest)
+                .atomic_output(atomic_output)
+                .build();
+            let summary = if asar_to_stdout {
+                process.write_asar(io::stdout())?
+            } else {
+                process.proceed()?
+            };
+            let reporter = Reporter {
+                verbose: args.verbose,
+                quiet: args.quiet,
+                largest_files: print_files_size,
+                stdout_painter: Painter::new(
+                    args.color,
+                    env::var_os("NO_COLOR"),
+                    io::stdout().is_terminal(),
+                ),
+                stderr_painter: Painter::new(
+                    args.color,
+                    env::var_os("NO_COLOR"),
+                    io::stderr().is_terminal(),
+                ),
+            };
+            reporter.warnings(&summary, io::stderr().lock())?;
+            if !asar_to_stdout {
+                reporter.summary(&summary, dry_run, io::stdout().lock())?;
+            }
         }
 
         GenerateDesktop { output } => {
-            DesktopGenerator::new().write_to_output_dir(&app, target_platform, output)?;
+            desktop_generator.write_to_output_dir(&app, target_platform, output)?;
+        }
+
+        GenerateMetainfo { output } => {
+            metainfo_generator.write_to_output_dir(&app, target_platform, output)?;
+        }
+
+        GenerateFlatpak { output } => {
+            FlatpakGenerator::new().write_to_output_dir(&app, target_platform, output)?;
+        }
+
+        Diff { filelist, files } => {
+            let old = parse_asar_filelist(&fs::read_to_string(root.join(filelist))?)?;
+            let new = files
+                .apply(PackingProcessBuilder::new(app).target_environment(target_environment))?
+                .build()
+                .resolve_asar_filelist()?;
+            print_diff(&FileListDiff::new(&old, &new), io::stdout().lock())?;
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        human_size, packed_message, parse_size, run, Args, ColorChoice, Command, Painter, Reporter,
+    };
+    use anyhow::Result;
+    use clap::Parser;
+    use electron_tasje::app::App;
+    use electron_tasje::pack::{
+        parse_asar_filelist, FileListDiff, PackSummary, PackWarning, PackingProcessBuilder,
+    };
+    use std::env::current_dir;
+    use std::fs;
+
+    #[test]
+    fn test_root() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/cli_root");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir)?;
+        }
+        run(Args::try_parse_from([
+            "tasje",
+            "--root",
+            "test_assets",
+            "pack",
+            "--no-icons",
+            "--output",
+            output_dir.to_str().unwrap(),
+        ])?)?;
+        assert!(output_dir.join("resources/app.asar").is_file());
+
+        // relative paths are resolved against the root, not the current dir
+        run(Args::try_parse_from([
+            "tasje",
+            "--root",
+            "test_assets",
+            "--config",
+            "config/electron-builder.js",
+            "pack",
+            "--no-icons",
+            "--dry-run",
+        ])?)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_options() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/cli_diff_options");
+        let options = ["--no-node-modules", "--include-sourcemaps"];
+        run(Args::try_parse_from(
+            [
+                "tasje",
+                "--root",
+                "test_assets",
+                "pack",
+                "--no-icons",
+                "--asar-filelist",
+                "--output",
+                output_dir.to_str().unwrap(),
+            ]
+            .into_iter()
+            .chain(options),
+        )?)?;
+        let filelist = output_dir.join("resources/app.asar.filelist");
+        let old = parse_asar_filelist(&fs::read_to_string(&filelist)?)?;
+        assert!(!old
+            .keys()
+            .any(|path| path.starts_with("node_modules/")));
+
+        let args = Args::try_parse_from(
+            ["tasje", "diff", filelist.to_str().unwrap()]
+                .into_iter()
+                .chain(options),
+        )?;
+        let Command::Diff { files, .. } = args.command else {
+            unreachable!()
+        };
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        let new = files
+            .apply(PackingProcessBuilder::new(app).base_output_dir(&output_dir))?
+            .build()
+            .resolve_asar_filelist()?;
+        assert!(FileListDiff::new(&old, &new).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_quiet() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/cli_quiet");
+        let args = Args::try_parse_from([
+            "tasje",
+            "--quiet",
+            "--root",
+            "test_assets",
+            "pack",
+            "--no-icons",
+            "--output",
+            output_dir.to_str().unwrap(),
+        ])?;
+        assert!(args.quiet);
+        run(args)?;
+        assert!(Args::try_parse_from(["tasje", "-q", "-v", "pack"]).is_err());
+
+        let summary = PackSummary {
+            warnings: vec![PackWarning::InvalidExtraMetadata],
+            ..Default::default()
+        };
+        let painter = Painter::new(ColorChoice::Never, None, false);
+        for quiet in [true, false] {
+            let reporter = Reporter {
+                verbose: if quiet { 0 } else { 2 },
+                quiet,
+                largest_files: Some(20),
+                stdout_painter: painter,
+                stderr_painter: painter,
+            };
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            reporter.warnings(&summary, &mut err)?;
+            reporter.summary(&summary, false, &mut out)?;
+            assert_eq!(out.is_empty(), quiet);
+            assert_eq!(err.is_empty(), quiet);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(5 << 20), "5.0 MiB");
+        assert_eq!(human_size(3 << 40), "3072.0 GiB");
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1GB"), Ok(1 << 30));
+        assert_eq!(parse_size("512M"), Ok(512 << 20));
+        assert_eq!(parse_size("64kib"), Ok(64 << 10));
+        assert_eq!(parse_size("1000"), Ok(1000));
+        assert!(parse_size("lots").is_err());
+    }
+
+    #[test]
+    fn test_no_color() {
+        let summary = PackSummary::default();
+        let colored = packed_message(&summary, Painter::new(ColorChoice::Auto, None, true));
+        assert!(colored.contains('\x1b'));
+
+        for painter in [
+            Painter::new(ColorChoice::Auto, Some("1".into()), true),
+            Painter::new(ColorChoice::Auto, None, false),
+            Painter::new(ColorChoice::Never, None, true),
+        ] {
+            let message = packed_message(&summary, painter);
+            assert!(!message.contains('\x1b'));
+            assert!(message.starts_with("packed 0 files"));
+        }
+        // an empty NO_COLOR is the same as unset
+        assert!(Painter::new(ColorChoice::Auto, Some("".into()), true).enabled);
+        assert!(Painter::new(ColorChoice::Always, Some("1".into()), false).enabled);
+    }
+}
@@ -1,3 +1,5 @@
+use anyhow::{bail, Result};
+use asar::HashAlgorithm;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Deserializer};
 use smart_default::SmartDefault;
@@ -5,6 +7,49 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 use crate::environment::Platform;
+use crate::utils::expand_home;
+
+/// 4 MiB, hardcoded in the asar crate
+const INTEGRITY_BLOCK_SIZE: usize = 4 * 1024 * 1024;
+
+/// tasje-specific, named lists of globs for common app shapes, added after "files"
+const FILE_PRESETS: &[(&str, &[&str])] = &[
+    (
+        "minimal-node",
+        &[
+            "!**/node_modules/**/{test,tests,__mocks__,doc,docs,benchmark,benchmarks,coverage}/**",
+            "!**/node_modules/**/*.{ts,tsx,map,flow,coffee}",
+        ],
+    ),
+    ("include-sourcemaps", &["**/*.map"]),
+];
+
+/// globs of the presets with these names, in the given order
+pub fn preset_files<S>(names: &[S]) -> Result<Vec<CopyDef>>
+where
+    S: AsRef<str>,
+{
+    let mut files = Vec::new();
+    for name in names {
+        let name = name.as_ref();
+        let Some((_, globs)) = FILE_PRESETS.iter().find(|(n, _)| *n == name) else {
+            bail!(
+                "unknown preset: {name:?}, available: {}",
+                FILE_PRESETS
+                    .iter()
+                    .map(|(n, _)| *n)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        };
+        files.extend(
+            globs
+                .iter()
+                .map(|g| CopyDef::Simple(g.to_string())),
+        );
+    }
+    Ok(files)
+}
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq, Default)]
 #[serde(rename_all = "camelCase")]
@@ -17,11 +62,13 @@ pub struct FileSet {
 }
 
 impl FileSet {
+    /// without the leading `./` and trailing slashes, which don't change what's matched
     pub fn from(&self) -> Option<&str> {
         self.from
             .as_ref()
             .and_then(|f| f.strip_prefix("./"))
             .or(self.from.as_deref())
+            .map(|f| f.trim_end_matches('/'))
     }
 
     pub fn to(&self) -> Option<&str> {
@@ -34,6 +81,12 @@ impl FileSet {
     pub fn filters(&self) -> &[String] {
         &self.filter
     }
+
+    /// a set with just `to` would copy the whole project into it,
+    /// which is almost certainly a mistake
+    pub fn is_unbounded(&self) -> bool {
+        self.from.is_none() && self.filter.iter().all(|f| f.starts_with('!'))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
@@ -48,6 +101,7 @@ pub enum CopyDef {
 pub struct EBDirectories {
     pub output: Option<String>,
     pub build_resources: Option<String>,
+    pub app: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -62,13 +116,42 @@ pub struct ProtocolAssociation {
 pub struct FileAssociation {
     #[serde(deserialize_with = "might_be_single")]
     ext: Vec<String>,
-    pub mime_type: Option<String>,
+    #[serde(default, deserialize_with = "might_be_single")]
+    mime_type: Vec<String>,
 }
 
 impl FileAssociation {
     pub fn exts(&self) -> &[String] {
         &self.ext
     }
+
+    pub fn mime_types(&self) -> &[String] {
+        &self.mime_type
+    }
+}
+
+/// tasje-specific, another executable shipped with the app, like a helper cli,
+/// which gets its own desktop entry
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdditionalExecutable {
+    pub executable_name: String,
+    /// "Name" in the desktop entry, defaults to the executable name
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// defaults to `<executableName>.desktop`
+    pub desktop_name: Option<String>,
+    /// whether it runs in a terminal
+    #[serde(default)]
+    pub terminal: bool,
+}
+
+impl AdditionalExecutable {
+    pub fn desktop_name(&self) -> String {
+        self.desktop_name
+            .clone()
+            .unwrap_or_else(|| format!("{}.desktop", self.executable_name))
+    }
 }
 
 fn might_be_single<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
@@ -107,6 +190,15 @@ impl<T> From<Vec<T>> for MightBeSingle<T> {
     }
 }
 
+/// either a bool, or an object with options, which also means enabled.
+/// none of the options are relevant to tasje
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum AsarOption {
+    Enabled(bool),
+    Options(serde::de::IgnoredAny),
+}
+
 #[derive(Debug, Clone, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct CommonOverridableProperties {
@@ -124,8 +216,18 @@ pub(crate) struct EBuilderBaseConfig {
 
     #[serde(default, deserialize_with = "might_be_single")]
     files: Vec<CopyDef>,
+    /// tasje-specific, names of glob presets to add after "files"
+    #[serde(default, deserialize_with = "might_be_single")]
+    preset: Vec<String>,
+    asar: Option<AsarOption>,
     #[serde(default, deserialize_with = "might_be_single")]
     asar_unpack: Vec<String>,
+    /// tasje-specific, globs to keep in the asar even if they match asarUnpack
+    #[serde(default, deserialize_with = "might_be_single")]
+    never_unpack: Vec<String>,
+    /// tasje-specific, globs to always leave out of the asar, along with the built-in ones
+    #[serde(default, deserialize_with = "might_be_single")]
+    default_excludes: Vec<String>,
     #[serde(default, deserialize_with = "might_be_single")]
     extra_files: Vec<CopyDef>,
     #[serde(default, deserialize_with = "might_be_single")]
@@ -142,11 +244,37 @@ pub(crate) struct EBuilderBaseConfig {
 
     #[serde(default)]
     extra_metadata: Option<serde_json::Value>,
+    copyright: Option<String>,
+    app_id: Option<String>,
+    build_version: Option<String>,
+    /// tasje-specific, keep package-lock.json and yarn.lock in the asar
+    keep_lockfiles: Option<bool>,
+    /// tasje-specific, hashing algorithm for the asar integrity
+    integrity_algorithm: Option<String>,
+    /// tasje-specific, size of blocks hashed separately for the asar integrity
+    integrity_block_size: Option<usize>,
 
     // "linux-specific" section
     #[serde(default, deserialize_with = "might_be_single")]
     category: Vec<String>,
     desktop: Option<HashMap<String, String>>,
+    #[serde(default, deserialize_with = "might_be_single")]
+    executable_args: Vec<String>,
+    /// tasje-specific, command to launch the executable with, like `env FOO=bar`
+    exec_wrapper: Option<String>,
+    /// tasje-specific, sizes that svg icons are rasterized to
+    #[serde(default)]
+    icon_sizes: Vec<u32>,
+    /// tasje-specific
+    #[serde(default, deserialize_with = "might_be_single")]
+    additional_executables: Vec<AdditionalExecutable>,
+
+    // "mac-specific" section
+    bundle_version: Option<String>,
+    bundle_short_version: Option<String>,
+
+    // "win-specific" section
+    legal_trademarks: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -188,6 +316,28 @@ impl<'a> EBuilderConfig {
         }
     }
 
+    pub fn presets(&'a self, platform: Platform) -> &'a [String] {
+        let platform_presets = &self.current_platform(platform).preset;
+        if !platform_presets.is_empty() {
+            platform_presets.as_slice()
+        } else {
+            self.base.preset.as_slice()
+        }
+    }
+
+    /// whether to pack the app into an asar, or copy it as a directory
+    pub fn asar(&'a self, platform: Platform) -> bool {
+        match self
+            .current_platform(platform)
+            .asar
+            .as_ref()
+            .or(self.base.asar.as_ref())
+        {
+            Some(AsarOption::Enabled(enabled)) => *enabled,
+            Some(AsarOption::Options(_)) | None => true,
+        }
+    }
+
     pub fn asar_unpack(&'a self, platform: Platform) -> &'a [String] {
         let platform_asar = &self.current_platform(platform).asar_unpack;
         if !platform_asar.is_empty() {
@@ -197,6 +347,24 @@ impl<'a> EBuilderConfig {
         }
     }
 
+    pub fn never_unpack(&'a self, platform: Platform) -> &'a [String] {
+        let platform_never = &self.current_platform(platform).never_unpack;
+        if !platform_never.is_empty() {
+            platform_never.as_slice()
+        } else {
+            self.base.never_unpack.as_slice()
+        }
+    }
+
+    pub fn default_excludes(&'a self, platform: Platform) -> &'a [String] {
+        let platform_excludes = &self.current_platform(platform).default_excludes;
+        if !platform_excludes.is_empty() {
+            platform_excludes.as_slice()
+        } else {
+            self.base.default_excludes.as_slice()
+        }
+    }
+
     pub fn extra_files(&'a self, platform: Platform) -> &'a [CopyDef] {
         let platform_extra = &self.current_platform(platform).extra_files;
         if !platform_extra.is_empty() {
@@ -222,6 +390,73 @@ impl<'a> EBuilderConfig {
             .or(self.base.extra_metadata.as_ref())
     }
 
+    /// reverse-DNS identifier of the app, like `com.example.app`
+    pub fn app_id(&'a self, platform: Platform) -> Option<&'a str> {
+        self.current_platform(platform)
+            .app_id
+            .as_deref()
+            .or(self.base.app_id.as_deref())
+    }
+
+    pub fn build_version(&'a self, platform: Platform) -> Option<&'a str> {
+        self.current_platform(platform)
+            .build_version
+            .as_deref()
+            .or(self.base.build_version.as_deref())
+    }
+
+    pub fn copyright(&'a self, platform: Platform) -> Option<&'a str> {
+        self.current_platform(platform)
+            .copyright
+            .as_deref()
+            .or(self.base.copyright.as_deref())
+    }
+
+    pub fn keep_lockfiles(&'a self, platform: Platform) -> bool {
+        self.current_platform(platform)
+            .keep_lockfiles
+            .or(self.base.keep_lockfiles)
+            .unwrap_or(false)
+    }
+
+    /// only what the asar crate can write is supported, which currently is SHA-256,
+    /// with 4 MiB blocks (the only values Electron supports as of writing)
+    pub fn integrity_algorithm(&'a self, platform: Platform) -> Result<HashAlgorithm> {
+        let block_size = self
+            .current_platform(platform)
+            .integrity_block_size
+            .or(self.base.integrity_block_size);
+        if let Some(size) = block_size.filter(|s| *s != INTEGRITY_BLOCK_SIZE) {
+            bail!("unsupported asar integrity block size: {size}, only {INTEGRITY_BLOCK_SIZE} is supported");
+        }
+        match self
+            .current_platform(platform)
+            .integrity_algorithm
+            .as_deref()
+            .or(self.base.integrity_algorithm.as_deref())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            None | Some("sha256") => Ok(HashAlgorithm::Sha256),
+            Some(other) => bail!("unsupported asar integrity algorithm: {other:?}"),
+        }
+    }
+
+    /// `CFBundleVersion` for the Info.plist, the build number
+    pub fn bundle_version(&'a self) -> Option<&'a str> {
+        self.mac.bundle_version.as_deref()
+    }
+
+    /// `CFBundleShortVersionString` for the Info.plist
+    pub fn bundle_short_version(&'a self) -> Option<&'a str> {
+        self.mac.bundle_short_version.as_deref()
+    }
+
+    /// `LegalTrademarks` in the version info of the windows executable
+    pub fn legal_trademarks(&'a self) -> Option<&'a str> {
+        self.win.legal_trademarks.as_deref()
+    }
+
     pub fn desktop_properties(&'a self, platform: Platform) -> Option<Vec<(String, String)>> {
         self.current_platform(platform)
             .desktop
@@ -230,6 +465,17 @@ impl<'a> EBuilderConfig {
             .map(|m| m.clone().into_iter().collect())
     }
 
+    pub fn additional_executables(&'a self, platform: Platform) -> &'a [AdditionalExecutable] {
+        let platform_executables = &self
+            .current_platform(platform)
+            .additional_executables;
+        if !platform_executables.is_empty() {
+            platform_executables.as_slice()
+        } else {
+            self.base.additional_executables.as_slice()
+        }
+    }
+
     pub fn output_dir(&'a self, platform: Platform) -> Option<&'a str> {
         self.current_platform(platform)
             .directories
@@ -238,7 +484,16 @@ impl<'a> EBuilderConfig {
             .or(self.base.directories.output.as_deref())
     }
 
-    pub fn protocol_associations(&'a self, platform: Platform) -> &[ProtocolAssociation] {
+    /// directory with the app sources to pack into the asar, relative to the project root
+    pub fn app_dir(&'a self, platform: Platform) -> Option<&'a str> {
+        self.current_platform(platform)
+            .directories
+            .app
+            .as_deref()
+            .or(self.base.directories.app.as_deref())
+    }
+
+    pub fn protocol_associations(&'a self, platform: Platform) -> &'a [ProtocolAssociation] {
         let platform_protocols = &self.current_platform(platform).protocols;
         if !platform_protocols.is_empty() {
             platform_protocols.as_slice()
@@ -257,10 +512,30 @@ impl<'a> EBuilderConfig {
     }
 
     /// https://specifications.freedesktop.org/menu-spec/latest/apa.html#main-category-registry
-    pub fn desktop_categories(&'a self, platform: Platform) -> &[String] {
+    pub fn desktop_categories(&'a self, platform: Platform) -> &'a [String] {
         &self.current_platform(platform).category
     }
 
+    /// arguments passed to the executable in the desktop entry
+    pub fn executable_args(&'a self, platform: Platform) -> &'a [String] {
+        &self.current_platform(platform).executable_args
+    }
+
+    pub fn exec_wrapper(&'a self, platform: Platform) -> Option<&'a str> {
+        self.current_platform(platform)
+            .exec_wrapper
+            .as_deref()
+    }
+
+    /// empty if not configured, which means the default sizes
+    pub fn icon_sizes(&'a self, platform: Platform) -> Result<&'a [u32]> {
+        let sizes = &self.current_platform(platform).icon_sizes;
+        if sizes.contains(&0) {
+            bail!("icon sizes have to be positive: {sizes:?}");
+        }
+        Ok(sizes)
+    }
+
     fn build_resources(&'a self, platform: Platform) -> &'a str {
         self.current_platform(platform)
             .directories
@@ -270,30 +545,39 @@ impl<'a> EBuilderConfig {
             .unwrap_or("build")
     }
 
-    pub(crate) fn icon_locations(&'a self) -> Vec<PathBuf> {
-        [
-            self.linux.icon.as_ref().map(PathBuf::from),
-            self.mac.icon.as_ref().map(PathBuf::from).or(Some(
-                Path::new(self.build_resources(Platform::Darwin)).join("icon.icns"),
-            )),
-            self.win.icon.as_ref().map(PathBuf::from).or(Some(
-                Path::new(self.build_resources(Platform::Windows)).join("icon.ico"),
-            )),
-            self.base.icon.as_ref().map(PathBuf::from),
-        ]
-        .into_iter()
-        .flatten()
-        .collect()
+    /// icons for the target platform, so that e.g. an svg for linux
+    /// is not mixed with the raster icons for windows and macOS.
+    /// without an icon in the config, the defaults from https://www.electron.build/icons are used
+    pub(crate) fn icon_locations(&'a self, platform: Platform) -> Vec<PathBuf> {
+        if let Some(icon) = self
+            .current_platform(platform)
+            .icon
+            .as_ref()
+            .or(self.base.icon.as_ref())
+        {
+            return vec![expand_home(icon)];
+        }
+        let build_resources = Path::new(self.build_resources(platform));
+        match platform {
+            // linux icons are generated from the macOS ones, if there is no icon set
+            Platform::Linux => vec![
+                build_resources.join("icons"),
+                build_resources.join("icon.icns"),
+            ],
+            Platform::Darwin => vec![build_resources.join("icon.icns")],
+            Platform::Windows => vec![build_resources.join("icon.ico")],
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::EBuilderConfig;
+    use super::{preset_files, EBuilderConfig};
     use crate::config::{CopyDef, FileSet};
     use crate::environment::Platform;
     use anyhow::Result;
     use serde_json::json;
+    use std::path::PathBuf;
 
     static LINUX: Platform = Platform::Linux;
 
@@ -379,4 +663,78 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_presets() -> Result<()> {
+        let bc: EBuilderConfig = serde_json::from_value(json!({
+            "preset": "include-sourcemaps",
+        }))?;
+        assert_eq!(bc.presets(LINUX), ["include-sourcemaps"]);
+        assert_eq!(
+            preset_files(bc.presets(LINUX))?,
+            [CopyDef::Simple("**/*.map".to_owned())]
+        );
+        assert!(preset_files(&["nonexistent"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_platform_metadata() -> Result<()> {
+        let bc: EBuilderConfig = serde_json::from_value(json!({
+            "mac": {
+                "bundleVersion": "1337",
+                "bundleShortVersion": "2.1",
+            },
+            "win": {
+                "legalTrademarks": "Tasje™",
+            },
+        }))?;
+        assert_eq!(bc.bundle_version(), Some("1337"));
+        assert_eq!(bc.bundle_short_version(), Some("2.1"));
+        assert_eq!(bc.legal_trademarks(), Some("Tasje™"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_icon_locations() -> Result<()> {
+        let bc: EBuilderConfig = serde_json::from_value(json!({
+            "icon": "icons/base.png",
+            "linux": {
+                "icon": "icons_linux",
+            },
+            "win": {
+                "icon": "icons_win/icon.ico",
+            },
+        }))?;
+        assert_eq!(
+            bc.icon_locations(Platform::Windows),
+            [PathBuf::from("icons_win/icon.ico")]
+        );
+        assert_eq!(bc.icon_locations(LINUX), [PathBuf::from("icons_linux")]);
+        assert_eq!(
+            bc.icon_locations(Platform::Darwin),
+            [PathBuf::from("icons/base.png")]
+        );
+
+        let bc: EBuilderConfig = serde_json::from_value(json!({
+            "icon": "~/assets/icon.png",
+        }))?;
+        let home = PathBuf::from(std::env::var_os("HOME").unwrap());
+        assert_eq!(bc.icon_locations(LINUX), [home.join("assets/icon.png")]);
+
+        let bc: EBuilderConfig = serde_json::from_value(json!({}))?;
+        assert_eq!(
+            bc.icon_locations(Platform::Windows),
+            [PathBuf::from("build/icon.ico")]
+        );
+        assert_eq!(
+            bc.icon_locations(LINUX),
+            [
+                PathBuf::from("build/icons"),
+                PathBuf::from("build/icon.icns")
+            ]
+        );
+
+        Ok(())
+    }
 }
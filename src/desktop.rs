@@ -1,40 +1,134 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use std::fs;
 use std::path::Path;
 
 use crate::app::App;
+use crate::config::{AdditionalExecutable, FileAssociation};
 use crate::environment::Platform;
+use crate::error::TasjeError;
 
+/// https://specifications.freedesktop.org/desktop-entry-spec/latest/recognized-keys.html
+static STANDARD_KEYS: &[&str] = &[
+    "Type",
+    "Version",
+    "Name",
+    "GenericName",
+    "NoDisplay",
+    "Comment",
+    "Icon",
+    "Hidden",
+    "OnlyShowIn",
+    "NotShowIn",
+    "DBusActivatable",
+    "TryExec",
+    "Exec",
+    "Path",
+    "Terminal",
+    "Actions",
+    "MimeType",
+    "Categories",
+    "Implements",
+    "Keywords",
+    "StartupNotify",
+    "StartupWMClass",
+    "URL",
+    "PrefersNonDefaultGPU",
+    "SingleMainWindow",
+];
+
+#[derive(Debug, Clone)]
 pub struct DesktopGenerator {
-    entries: Vec<(String, String)>,
+    lines: Vec<String>,
+    protocol_actions: bool,
+    icon: Option<String>,
+    crlf: bool,
+    always_field_code: bool,
 }
 
 impl DesktopGenerator {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
-            entries: Vec::new(),
+            lines: Vec::new(),
+            protocol_actions: false,
+            icon: None,
+            crlf: false,
+            always_field_code: false,
         }
     }
 
+    /// emit a desktop action for opening urls for every protocol scheme
+    pub fn protocol_actions(mut self, enabled: bool) -> Self {
+        self.protocol_actions = enabled;
+        self
+    }
+
+    /// end lines with `\r\n` instead of `\n`, for tooling expecting windows line endings
+    pub fn crlf(mut self, enabled: bool) -> Self {
+        self.crlf = enabled;
+        self
+    }
+
+    /// keep `%U` in `Exec` even if the app doesn't handle any urls or files
+    pub fn always_field_code(mut self, enabled: bool) -> Self {
+        self.always_field_code = enabled;
+        self
+    }
+
     fn add_entry<K, V>(&mut self, key: K, val: V)
     where
         K: AsRef<str>,
         V: AsRef<str>,
     {
-        self.entries
-            .push((String::from(key.as_ref()), String::from(val.as_ref())));
+        self.lines
+            .push(format!("{}={}", key.as_ref(), val.as_ref()));
+    }
+
+    fn add_comment<S: AsRef<str>>(&mut self, comment: S) {
+        self.lines.push(format!("# {}", comment.as_ref()));
+    }
+
+    /// explicit `Icon` value, like an absolute path to the icon.
+    /// by default, the executable name is used, as a name from the icon theme
+    pub fn icon<S: AsRef<str>>(mut self, icon: S) -> Self {
+        self.icon = Some(icon.as_ref().to_string());
+        self
     }
 
     /// https://www.freedesktop.org/wiki/Specifications/desktop-entry-spec/
-    pub fn generate(mut self, app: &App, platform: Platform) -> Result<String> {
+    pub fn generate(self, app: &App, platform: Platform) -> Result<String, TasjeError> {
+        self.generate_entry(app, platform)
+            .map_err(TasjeError::Desktop)
+    }
+
+    fn generate_entry(mut self, app: &App, platform: Platform) -> Result<String> {
         let exec_name = app.executable_name(platform)?;
 
         self.add_entry("Name", app.product_name(platform));
-        self.add_entry("Exec", format!("/usr/bin/{} %U", exec_name));
+        // validators warn about field codes in entries without a MimeType
+        let handles_urls = app
+            .config()
+            .protocol_associations(platform)
+            .iter()
+            .any(|p| !p.schemes.is_empty())
+            || app
+                .config()
+                .file_associations(platform)
+                .iter()
+                .any(|f| !association_mime_types(f).is_empty());
+        let command = exec_command(app, platform, &exec_name)?;
+        if handles_urls || self.always_field_code {
+            self.add_entry("Exec", format!("{command} %U"));
+        } else {
+            self.add_entry("Exec", &command);
+        }
         self.add_entry("Terminal", "false");
         self.add_entry("Type", "Application");
-        self.add_entry("Icon", exec_name);
+        let icon = self
+            .icon
+            .clone()
+            .unwrap_or_else(|| exec_name.clone());
+        self.add_entry("Icon", icon);
         if let Some(properties) = app.config().desktop_properties(platform) {
             // order might and will be random. serde_json has `preserve_order` feature,
             // but then EBuilderConfig internally parses it into a HashMap.
@@ -48,17 +142,36 @@ impl DesktopGenerator {
         }
 
         let mut mimes = vec![];
+        let mut actions = vec![];
+        let mut comments = vec![];
         for protocol in app.config().protocol_associations(platform) {
+            if let Some(name) = &protocol.name {
+                let handlers = protocol
+                    .schemes
+                    .iter()
+                    .map(|scheme| format!("x-scheme-handler/{scheme}"))
+                    .collect::<Vec<_>>();
+                comments.push(format!("{name}: {}", handlers.join(";")));
+            }
             for scheme in &protocol.schemes {
                 mimes.push(format!("x-scheme-handler/{}", scheme));
+                if self.protocol_actions {
+                    actions.push((
+                        format!("open-{}", action_safe_name(scheme)),
+                        format!("Open {} URL", scheme),
+                        format!("{command} %u"),
+                    ));
+                }
             }
         }
         for file_ass in app.config().file_associations(platform) {
-            if let Some(mime_type) = &file_ass.mime_type {
-                mimes.push(mime_type.clone());
-            }
+            mimes.extend(association_mime_types(file_ass));
         }
         if !mimes.is_empty() {
+            // the names of protocols, which have no place in the desktop entry otherwise
+            for comment in comments {
+                self.add_comment(comment);
+            }
             self.add_entry("MimeType", mimes.join(";"));
         }
 
@@ -67,12 +180,97 @@ impl DesktopGenerator {
             self.add_entry("Categories", categories.join(";"));
         }
 
+        if !actions.is_empty() {
+            let ids = actions
+                .iter()
+                .map(|(id, _, _)| id.as_str())
+                .collect::<Vec<_>>();
+            self.add_entry("Actions", format!("{};", ids.join(";")));
+        }
+
         let mut contents = String::from("[Desktop Entry]\n");
-        for (key, val) in self.entries {
-            contents.push_str(&format!("{key}={val}\n"));
+        for line in &self.lines {
+            contents.push_str(line);
+            contents.push('\n');
+        }
+        for (id, name, exec) in actions {
+            contents.push_str(&format!(
+                "\n[Desktop Action {id}]\nName={name}\nExec={exec}\n"
+            ));
         }
+        Ok(self.finish(contents))
+    }
 
-        Ok(contents)
+    fn finish(&self, mut contents: String) -> String {
+        // exactly one newline at the end
+        contents.truncate(contents.trim_end_matches('\n').len());
+        contents.push('\n');
+        if self.crlf {
+            contents = contents.replace('\n', "\r\n");
+        }
+        contents
+    }
+
+    /// entry for one of "additionalExecutables", sharing the icon and categories of the app
+    fn generate_additional_entry(
+        &self,
+        app: &App,
+        platform: Platform,
+        executable: &AdditionalExecutable,
+    ) -> Result<String> {
+        let mut entry = Self {
+            lines: Vec::new(),
+            ..self.clone()
+        };
+        let name = &executable.executable_name;
+        entry.add_entry("Name", executable.name.as_deref().unwrap_or(name));
+        entry.add_entry("Exec", format!("/usr/bin/{name}"));
+        entry.add_entry("Terminal", executable.terminal.to_string());
+        entry.add_entry("Type", "Application");
+        let icon = match &self.icon {
+            Some(icon) => icon.clone(),
+            None => app.executable_name(platform)?,
+        };
+        entry.add_entry("Icon", icon);
+        if let Some(comment) = &executable.description {
+            entry.add_entry("Comment", comment);
+        }
+        let categories = app.config().desktop_categories(platform);
+        if !categories.is_empty() {
+            entry.add_entry("Categories", categories.join(";"));
+        }
+
+        let mut contents = String::from("[Desktop Entry]\n");
+        for line in &entry.lines {
+            contents.push_str(line);
+            contents.push('\n');
+        }
+        Ok(entry.finish(contents))
+    }
+
+    /// file names and contents of the desktop entries of the app
+    /// and of its "additionalExecutables", starting with the app
+    pub fn generate_all(
+        self,
+        app: &App,
+        platform: Platform,
+    ) -> Result<Vec<(String, String)>, TasjeError> {
+        self.generate_all_entries(app, platform)
+            .map_err(TasjeError::Desktop)
+    }
+
+    fn generate_all_entries(self, app: &App, platform: Platform) -> Result<Vec<(String, String)>> {
+        let mut entries = vec![(
+            app.desktop_name(platform)?,
+            self.clone().generate_entry(app, platform)?,
+        )];
+        for executable in app.config().additional_executables(platform) {
+            entries.push((
+                executable.desktop_name(),
+                self.generate_additional_entry(app, platform, executable)?,
+            ));
+        }
+        Ok(entries)
     }
 
     pub fn write_to_output_dir<P>(
@@ -80,11 +278,25 @@ impl DesktopGenerator {
         app: &App,
         platform: Platform,
         output: Option<P>,
+    ) -> Result<(), TasjeError>
+    where
+        P: AsRef<Path>,
+    {
+        let entries = self.generate_all(app, platform)?;
+        Self::write_entries(app, platform, output, entries).map_err(TasjeError::Desktop)
+    }
+
+    /// with `output` being a file, the entries of the additional executables
+    /// are written next to it
+    fn write_entries<P>(
+        app: &App,
+        platform: Platform,
+        output: Option<P>,
+        entries: Vec<(String, String)>,
     ) -> Result<()>
     where
         P: AsRef<Path>,
     {
-        let contents = self.generate(app, platform)?;
         let mut target = app.output_dir(platform);
         if let Some(out) = output {
             target = target.join(out.as_ref());
@@ -94,25 +306,199 @@ impl DesktopGenerator {
         } else {
             target = target.join(app.desktop_name(platform)?);
         }
+        let dir = target
+            .parent()
+            .ok_or_else(|| anyhow!("no desktop entry target parent"))?
+            .to_path_buf();
 
         // make sure dir exists
-        fs::create_dir_all(
-            target
-                .parent()
-                .ok_or_else(|| anyhow!("no desktop entry target parent"))?,
-        )?;
-        fs::write(target, contents)?;
+        fs::create_dir_all(&dir)?;
+        for (i, (file_name, contents)) in entries.into_iter().enumerate() {
+            if i == 0 {
+                fs::write(&target, contents)?;
+            } else {
+                fs::write(dir.join(file_name), contents)?;
+            }
+        }
 
         Ok(())
     }
 }
 
+/// keys from the `desktop` config that are neither standard nor `X-` extensions,
+/// which desktop environments are free to reject
+pub fn nonstandard_keys(app: &App, platform: Platform) -> Vec<String> {
+    app.config()
+        .desktop_properties(platform)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, _)| key)
+        .filter(|key| {
+            // localized keys, like `Name[pl]`
+            let base = key.split('[').next().unwrap_or(key);
+            !key.starts_with("X-") && !STANDARD_KEYS.contains(&base)
+        })
+        .collect()
+}
+
+/// paths of protocol and file associations without any schemes or extensions,
+/// which are most likely mistakes, as they don't register anything
+pub fn empty_associations(app: &App, platform: Platform) -> Vec<String> {
+    let config = app.config();
+    let protocols = config
+        .protocol_associations(platform)
+        .iter()
+        .enumerate()
+        .filter(|(_, protocol)| protocol.schemes.is_empty())
+        .map(|(i, _)| format!("protocols[{i}].schemes"));
+    let files = config
+        .file_associations(platform)
+        .iter()
+        .enumerate()
+        .filter(|(_, association)| association.exts().is_empty())
+        .map(|(i, _)| format!("fileAssociations[{i}].ext"));
+    protocols.chain(files).collect()
+}
+
+/// the command in `Exec`, without the field code: the executable, with the wrapper
+/// and arguments from the config
+fn exec_command(app: &App, platform: Platform, exec_name: &str) -> Result<String> {
+    let config = app.config();
+    let mut parts = Vec::new();
+    if let Some(wrapper) = config.exec_wrapper(platform) {
+        // used as it is, so that it can have multiple arguments
+        check_field_codes(wrapper)?;
+        parts.push(wrapper.to_string());
+    }
+    parts.push(format!("/usr/bin/{exec_name}"));
+    for arg in config.executable_args(platform) {
+        check_field_codes(arg)?;
+        parts.push(exec_quote(arg));
+    }
+    Ok(parts.join(" "))
+}
+
+/// field codes, like `%U`, are added by tasje, and a literal `%` has to be written as `%%`
+fn check_field_codes(value: &str) -> Result<()> {
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '%' {
+            match chars.next() {
+                Some('%') => {}
+                Some(code) => bail!("field code %{code} is not allowed in the exec arguments, use %% for a literal %: {value:?}"),
+                None => bail!("unescaped % in the exec arguments, use %% for a literal %: {value:?}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// quotes an argument with reserved characters, like spaces. the general backslash escapes
+/// are applied before unquoting, so the backslashes in quoted arguments are doubled
+fn exec_quote(arg: &str) -> String {
+    const RESERVED: &[char] = &[
+        ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(',
+        ')', '`',
+    ];
+    if !arg.is_empty() && !arg.contains(RESERVED) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for ch in arg.chars() {
+        match ch {
+            '"' | '`' | '$' => quoted.push_str(&format!("\\\\{ch}")),
+            '\\' => quoted.push_str("\\\\\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// mime types of common extensions, for file associations without a mimeType
+static EXTENSION_MIME_TYPES: &[(&str, &str)] = &[
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("js", "text/javascript"),
+    ("ics", "text/calendar"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("pdf", "application/pdf"),
+    ("epub", "application/epub+zip"),
+    ("zip", "application/zip"),
+    ("tar", "application/x-tar"),
+    ("gz", "application/gzip"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("flac", "audio/flac"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+];
+
+fn extension_mime_type(ext: &str) -> Option<&'static str> {
+    let ext = ext.trim_start_matches('.').to_ascii_lowercase();
+    EXTENSION_MIME_TYPES
+        .iter()
+        .find(|(e, _)| *e == ext)
+        .map(|(_, mime)| *mime)
+}
+
+/// mimeType of the association, or if there is none, the mime types inferred
+/// from the extensions that are known
+fn association_mime_types(association: &FileAssociation) -> Vec<String> {
+    if !association.mime_types().is_empty() {
+        return association.mime_types().to_vec();
+    }
+    association
+        .exts()
+        .iter()
+        .filter_map(|ext| extension_mime_type(ext))
+        .map(str::to_string)
+        .collect()
+}
+
+/// extensions of file associations without a mimeType, with the mime types inferred for them
+pub fn inferred_mime_types(app: &App, platform: Platform) -> Vec<(String, String)> {
+    app.config()
+        .file_associations(platform)
+        .iter()
+        .filter(|association| association.mime_types().is_empty())
+        .flat_map(|association| association.exts())
+        .filter_map(|ext| extension_mime_type(ext).map(|mime| (ext.clone(), mime.to_string())))
+        .collect()
+}
+
+/// action identifiers may only contain alphanumerics and dashes
+fn action_safe_name(name: &str) -> String {
+    name.chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '-' })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::DesktopGenerator;
+    use super::{
+        empty_associations, exec_quote, inferred_mime_types, nonstandard_keys, DesktopGenerator,
+    };
     use crate::app::App;
     use crate::environment::Platform;
+    use crate::package::Package;
     use anyhow::Result;
+    use serde_json::json;
+    use std::env::current_dir;
+    use std::fs;
 
     static LINUX: Platform = Platform::Linux;
 
@@ -132,6 +518,8 @@ Type=Application
 Icon=tasje
 CustomField=custom_value
 Comment=Packs Electron apps
+# tasje: x-scheme-handler/tasje
+# ebuilder: x-scheme-handler/ebuilder;x-scheme-handler/electron-builder
 MimeType=x-scheme-handler/tasje;x-scheme-handler/ebuilder;x-scheme-handler/electron-builder;application/x-tas
 Categories=Tools
 "#
@@ -139,4 +527,385 @@ Categories=Tools
 
         Ok(())
     }
+
+    #[test]
+    fn test_gen_desktop_unicode() -> Result<()> {
+        let app = App::new(
+            Package::try_from(json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "productName": "タスジェ",
+                "description": "エレクトロンのアプリを詰める",
+            }))?,
+            serde_json::from_value(json!({}))?,
+            "test_assets".into(),
+        );
+
+        let desktop = DesktopGenerator::new().generate(&app, LINUX)?;
+        assert!(desktop.contains("\nName=タスジェ\n"));
+        assert!(desktop.contains("\nExec=/usr/bin/tasje\n"));
+        assert!(desktop.contains("\nComment=エレクトロンのアプリを詰める\n"));
+        assert_eq!(app.executable_name(LINUX)?, "tasje");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gen_desktop_protocol_actions() -> Result<()> {
+        let app = App::new(
+            Package::try_from(json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }))?,
+            serde_json::from_value(json!({
+                "protocols": {
+                    "name": "tasje",
+                    "schemes": ["tasje", "tasje+ssh"],
+                },
+            }))?,
+            "test_assets".into(),
+        );
+
+        let desktop = DesktopGenerator::new()
+            .protocol_actions(true)
+            .generate(&app, LINUX)?;
+        assert!(desktop.contains("\nActions=open-tasje;open-tasje-ssh;\n"));
+        assert!(desktop.ends_with(
+            r#"
+[Desktop Action open-tasje]
+Name=Open tasje URL
+Exec=/usr/bin/tasje %u
+
+[Desktop Action open-tasje-ssh]
+Name=Open tasje+ssh URL
+Exec=/usr/bin/tasje %u
+"#
+        ));
+
+        let desktop = DesktopGenerator::new().generate(&app, LINUX)?;
+        assert!(!desktop.contains("Desktop Action"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gen_desktop_icon() -> Result<()> {
+        let app: App = App::new_from_package_file("test_assets/package.json")?;
+
+        let desktop = DesktopGenerator::new().generate(&app, LINUX)?;
+        assert!(desktop.contains("\nIcon=tasje\n"));
+
+        let desktop = DesktopGenerator::new()
+            .icon("/opt/tasje/icons/256x256.png")
+            .generate(&app, LINUX)?;
+        assert!(desktop.contains("\nIcon=/opt/tasje/icons/256x256.png\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gen_desktop_mime_types() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "fileAssociations": [{
+                    "ext": ["tas", "tasje"],
+                    "mimeType": ["application/x-tas", "application/x-tasje"],
+                }, {
+                    "ext": "ebuilder",
+                }],
+            }),
+            "test_assets".into(),
+        )?;
+
+        let desktop = DesktopGenerator::new().generate(&app, LINUX)?;
+        assert!(desktop.contains("\nMimeType=application/x-tas;application/x-tasje\n"));
+        assert!(desktop.contains("\nExec=/usr/bin/tasje %U\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_associations() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "protocols": [{
+                    "name": "Tasje",
+                    "schemes": ["tasje"],
+                }, {
+                    "name": "Nothing",
+                    "schemes": [],
+                }],
+                "fileAssociations": [{
+                    "ext": [],
+                }, {
+                    "ext": "tas",
+                }],
+            }),
+            "test_assets".into(),
+        )?;
+        assert_eq!(
+            empty_associations(&app, LINUX),
+            ["protocols[1].schemes", "fileAssociations[0].ext"]
+        );
+
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        assert!(empty_associations(&app, LINUX).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gen_desktop_exec_args() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "protocols": {
+                    "schemes": ["tasje"],
+                },
+                "linux": {
+                    "executableArgs": ["--ozone-platform-hint=auto", "--title=Tasje 100%%"],
+                    "execWrapper": "env TASJE=1",
+                },
+            }),
+            "test_assets".into(),
+        )?;
+
+        let desktop = DesktopGenerator::new()
+            .protocol_actions(true)
+            .generate(&app, LINUX)?;
+        assert!(desktop.contains(
+            "\nExec=env TASJE=1 /usr/bin/tasje --ozone-platform-hint=auto \"--title=Tasje 100%%\" %U\n"
+        ));
+        assert!(desktop.contains(
+            "\nExec=env TASJE=1 /usr/bin/tasje --ozone-platform-hint=auto \"--title=Tasje 100%%\" %u\n"
+        ));
+
+        for args in [json!(["--file=%f"]), json!(["100%"])] {
+            let app = App::from_values(
+                json!({
+                    "name": "tasje",
+                    "version": "1.0.0",
+                }),
+                json!({
+                    "linux": {
+                        "executableArgs": args,
+                    },
+                }),
+                "test_assets".into(),
+            )?;
+            assert!(DesktopGenerator::new()
+                .generate(&app, LINUX)
+                .is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exec_quote() {
+        assert_eq!(exec_quote("--no-sandbox"), "--no-sandbox");
+        assert_eq!(exec_quote(""), r#""""#);
+        assert_eq!(exec_quote("a b"), r#""a b""#);
+        assert_eq!(exec_quote(r#"say "hi""#), r#""say \\"hi\\"""#);
+        assert_eq!(exec_quote("$HOME"), r#""\\$HOME""#);
+        assert_eq!(exec_quote(r"C:\dir"), r#""C:\\\\dir""#);
+    }
+
+    #[test]
+    fn test_gen_desktop_inferred_mime_types() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "fileAssociations": [{
+                    "ext": ["txt", "unknown"],
+                }, {
+                    "ext": "md",
+                    "mimeType": "text/x-tasje",
+                }],
+            }),
+            "test_assets".into(),
+        )?;
+
+        let desktop = DesktopGenerator::new().generate(&app, LINUX)?;
+        assert!(desktop.contains("\nMimeType=text/plain;text/x-tasje\n"));
+        assert!(desktop.contains("\nExec=/usr/bin/tasje %U\n"));
+        assert_eq!(
+            inferred_mime_types(&app, LINUX),
+            [("txt".to_string(), "text/plain".to_string())]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gen_desktop_field_code() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "fileAssociations": {
+                    "ext": "tas",
+                },
+            }),
+            "test_assets".into(),
+        )?;
+
+        let desktop = DesktopGenerator::new().generate(&app, LINUX)?;
+        assert!(desktop.contains("\nExec=/usr/bin/tasje\n"));
+        assert!(!desktop.contains("MimeType"));
+
+        let desktop = DesktopGenerator::new()
+            .always_field_code(true)
+            .generate(&app, LINUX)?;
+        assert!(desktop.contains("\nExec=/usr/bin/tasje %U\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gen_desktop_custom_keys() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "desktop": {
+                    "X-GNOME-UsesNotifications": "true",
+                    "SingleMainWindow": "true",
+                    "Keywords[pl]": "pakowanie;",
+                    "UsesNotifications": "true",
+                },
+            }),
+            "test_assets".into(),
+        )?;
+
+        let desktop = DesktopGenerator::new().generate(&app, LINUX)?;
+        assert!(desktop.contains("\nX-GNOME-UsesNotifications=true\n"));
+        assert!(desktop.contains("\nSingleMainWindow=true\n"));
+        assert_eq!(nonstandard_keys(&app, LINUX), vec!["UsesNotifications"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gen_desktop_line_endings() -> Result<()> {
+        let app: App = App::new_from_package_file("test_assets/package.json")?;
+
+        let desktop = DesktopGenerator::new()
+            .protocol_actions(true)
+            .generate(&app, LINUX)?;
+        assert!(desktop.ends_with("%u\n") && !desktop.ends_with("\n\n"));
+        assert!(!desktop.contains('\r'));
+
+        let crlf = DesktopGenerator::new()
+            .protocol_actions(true)
+            .crlf(true)
+            .generate(&app, LINUX)?;
+        assert!(crlf.ends_with("%u\r\n") && !crlf.ends_with("\r\n\r\n"));
+        assert_eq!(crlf.matches("\r\n").count(), desktop.matches('\n').count());
+        assert_eq!(crlf.replace("\r\n", "\n"), desktop);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gen_desktop_additional_executables() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "productName": "Tasje",
+            }),
+            json!({
+                "linux": {
+                    "category": "Development",
+                    "additionalExecutables": [{
+                        "executableName": "tasje-cli",
+                        "name": "Tasje CLI",
+                        "description": "Packs apps from the terminal",
+                        "terminal": true,
+                    }],
+                },
+            }),
+            "test_assets".into(),
+        )?;
+
+        let entries = DesktopGenerator::new().generate_all(&app, LINUX)?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, "tasje.desktop");
+        assert_eq!(
+            entries[1],
+            (
+                "tasje-cli.desktop".to_string(),
+                r#"[Desktop Entry]
+Name=Tasje CLI
+Exec=/usr/bin/tasje-cli
+Terminal=true
+Type=Application
+Icon=tasje
+Comment=Packs apps from the terminal
+Categories=Development
+"#
+                .to_string()
+            )
+        );
+
+        let output_dir = current_dir()?.join(".test-workspace/desktop_additional_executables");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir)?;
+        }
+        fs::create_dir_all(&output_dir)?;
+        DesktopGenerator::new().write_to_output_dir(&app, LINUX, Some(&output_dir))?;
+        let mut files = fs::read_dir(&output_dir)?
+            .map(|e| Ok(e?.file_name().to_string_lossy().to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        files.sort();
+        assert_eq!(files, ["tasje-cli.desktop", "tasje.desktop"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gen_desktop_protocol_names() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "protocols": [{
+                    "name": "Tasje Link",
+                    "schemes": ["tasje"],
+                }, {
+                    "schemes": ["unnamed"],
+                }],
+            }),
+            "test_assets".into(),
+        )?;
+
+        let desktop = DesktopGenerator::new().generate(&app, LINUX)?;
+        assert!(desktop.contains(
+            "\n# Tasje Link: x-scheme-handler/tasje\nMimeType=x-scheme-handler/tasje;x-scheme-handler/unnamed\n"
+        ));
+        assert_eq!(desktop.matches("\n# ").count(), 1);
+
+        Ok(())
+    }
 }
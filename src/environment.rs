@@ -5,6 +5,7 @@
 //! extend it, and feel free to send a pull request :)
 
 use anyhow::{bail, Result};
+use std::fmt;
 
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -13,9 +14,17 @@ pub enum Architecture {
     X86,
     Aarch64,
     ArmV7,
+    /// macOS apps with both x86_64 and aarch64 binaries.
+    /// tasje doesn't merge the binaries, they have to be universal already
+    Universal,
 }
 
 impl Architecture {
+    pub fn all() -> &'static [Architecture] {
+        use Architecture::*;
+        &[X86_64, X86, Aarch64, ArmV7, Universal]
+    }
+
     pub fn from_tasje_name<N>(name: N) -> Result<Architecture>
     where
         N: AsRef<str>,
@@ -26,10 +35,22 @@ impl Architecture {
             "x86" => Ok(X86),
             "aarch64" => Ok(Aarch64),
             "armv7" => Ok(ArmV7),
+            "universal" => Ok(Universal),
             n => bail!("unknown architecture name: {n:?}"),
         }
     }
 
+    pub fn to_tasje(&self) -> &'static str {
+        use Architecture::*;
+        match self {
+            X86_64 => "x86_64",
+            X86 => "x86",
+            Aarch64 => "aarch64",
+            ArmV7 => "armv7",
+            Universal => "universal",
+        }
+    }
+
     pub fn to_node(&self) -> &'static str {
         use Architecture::*;
         match self {
@@ -37,10 +58,31 @@ impl Architecture {
             X86 => "ia32",
             Aarch64 => "arm64",
             ArmV7 => "arm",
+            // not a node.js arch, but what electron-builder uses for `${arch}`
+            Universal => "universal",
+        }
+    }
+
+    /// the real architectures that binaries are built for
+    pub fn components(&self) -> &'static [Architecture] {
+        use Architecture::*;
+        match self {
+            X86_64 => &[X86_64],
+            X86 => &[X86],
+            Aarch64 => &[Aarch64],
+            ArmV7 => &[ArmV7],
+            Universal => &[X86_64, Aarch64],
         }
     }
 }
 
+/// the name accepted by [`Architecture::from_tasje_name`]
+impl fmt::Display for Architecture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_tasje())
+    }
+}
+
 #[cfg(target_arch = "x86_64")]
 pub static HOST_ARCHITECTURE: Architecture = Architecture::X86_64;
 
@@ -62,6 +104,11 @@ pub enum Platform {
 }
 
 impl Platform {
+    pub fn all() -> &'static [Platform] {
+        use Platform::*;
+        &[Linux, Windows, Darwin]
+    }
+
     pub fn from_tasje_name<N>(name: N) -> Result<Platform>
     where
         N: AsRef<str>,
@@ -75,6 +122,15 @@ impl Platform {
         }
     }
 
+    pub fn to_tasje(&self) -> &'static str {
+        use Platform::*;
+        match self {
+            Linux => "linux",
+            Windows => "windows",
+            Darwin => "darwin",
+        }
+    }
+
     pub fn to_node(&self) -> &'static str {
         use Platform::*;
         match self {
@@ -85,6 +141,13 @@ impl Platform {
     }
 }
 
+/// the name accepted by [`Platform::from_tasje_name`]
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_tasje())
+    }
+}
+
 #[cfg(target_os = "linux")]
 pub static HOST_PLATFORM: Platform = Platform::Linux;
 
@@ -100,7 +163,119 @@ pub struct Environment {
     pub platform: Platform,
 }
 
+impl Environment {
+    /// fails early on combinations that electron has no builds for
+    pub fn check_supported(&self) -> Result<()> {
+        use Architecture::*;
+        use Platform::*;
+        match (self.platform, self.architecture) {
+            (Darwin, X86 | ArmV7) | (Windows, ArmV7) => bail!(
+                "electron does not support the {} architecture on {} targets",
+                self.architecture.to_node(),
+                self.platform.to_node(),
+            ),
+            (Linux | Windows, Universal) => bail!(
+                "universal builds are only supported on darwin targets, not {}",
+                self.platform.to_node(),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
 pub static HOST_ENVIRONMENT: Environment = Environment {
     architecture: HOST_ARCHITECTURE,
     platform: HOST_PLATFORM,
 };
+
+#[cfg(test)]
+mod tests {
+    use super::{Architecture, Environment, Platform};
+
+    #[test]
+    fn test_display() {
+        assert_eq!(format!("{}", Platform::Windows), "windows");
+        assert_eq!(Architecture::X86_64.to_string(), "x86_64");
+        for platform in [Platform::Linux, Platform::Windows, Platform::Darwin] {
+            assert_eq!(
+                Platform::from_tasje_name(platform.to_string()).unwrap(),
+                platform
+            );
+        }
+        for arch in [
+            Architecture::X86_64,
+            Architecture::X86,
+            Architecture::Aarch64,
+            Architecture::ArmV7,
+            Architecture::Universal,
+        ] {
+            assert_eq!(
+                Architecture::from_tasje_name(arch.to_string()).unwrap(),
+                arch
+            );
+        }
+    }
+
+    #[test]
+    fn test_all() {
+        assert_eq!(Platform::all().len(), 3);
+        for platform in Platform::all() {
+            assert_eq!(
+                Platform::from_tasje_name(platform.to_tasje()).unwrap(),
+                *platform
+            );
+        }
+        assert_eq!(Architecture::all().len(), 5);
+        for arch in Architecture::all() {
+            assert_eq!(
+                Architecture::from_tasje_name(arch.to_tasje()).unwrap(),
+                *arch
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_supported() {
+        let supported = Environment {
+            architecture: Architecture::Aarch64,
+            platform: Platform::Darwin,
+        };
+        assert!(supported.check_supported().is_ok());
+
+        let unsupported = Environment {
+            architecture: Architecture::ArmV7,
+            platform: Platform::Windows,
+        };
+        assert_eq!(
+            unsupported
+                .check_supported()
+                .unwrap_err()
+                .to_string(),
+            "electron does not support the arm architecture on win32 targets"
+        );
+    }
+
+    #[test]
+    fn test_universal() {
+        let universal = Environment {
+            architecture: Architecture::Universal,
+            platform: Platform::Darwin,
+        };
+        assert!(universal.check_supported().is_ok());
+        assert_eq!(
+            Architecture::Universal.components(),
+            [Architecture::X86_64, Architecture::Aarch64]
+        );
+        assert_eq!(Architecture::Aarch64.components(), [Architecture::Aarch64]);
+        assert_eq!(
+            crate::utils::fill_variable_template("tasje-${arch}.zip", universal).unwrap(),
+            "tasje-universal.zip"
+        );
+
+        let linux = Environment {
+            platform: Platform::Linux,
+            ..universal
+        };
+        assert!(linux.check_supported().is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+use crate::app::AppParseError;
+use crate::pack::PackWarning;
+
+/// errors from the public api, grouped by what was being done when they happened.
+/// the wrapped errors hold the details, like which file failed
+#[non_exhaustive]
+#[derive(Error, Debug)]
+pub enum TasjeError {
+    #[error(transparent)]
+    Parse(#[from] AppParseError),
+    #[error("invalid configuration: {0:#}")]
+    Config(anyhow::Error),
+    #[error("on finding the files to pack: {0:#}")]
+    Walk(anyhow::Error),
+    #[error("on writing the asar: {0:#}")]
+    Asar(anyhow::Error),
+    #[error("on copying extra files: {0:#}")]
+    ExtraFiles(anyhow::Error),
+    #[error("on generating icons: {0:#}")]
+    Icon(anyhow::Error),
+    #[error("on generating the desktop entry: {0:#}")]
+    Desktop(anyhow::Error),
+    #[error("on generating the metainfo: {0:#}")]
+    Metainfo(anyhow::Error),
+    #[error("on generating the flatpak manifest: {0:#}")]
+    Flatpak(anyhow::Error),
+    #[error("on moving the output into place: {0:#}")]
+    Output(anyhow::Error),
+    #[error("{path:?} is not readable, make it readable or run tasje as a user that can read it")]
+    PermissionDenied {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// the file was found when walking, but was gone when reading it
+    #[error("{path:?} does not exist anymore, was it removed while packing?")]
+    FileNotFound {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// a warning that was turned into an error by one of the strict options
+    #[error(transparent)]
+    Strict(PackWarning),
+    #[error(transparent)]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+}
+
+impl TasjeError {
+    /// keeps errors that were already classified deeper down,
+    /// otherwise uses `kind` for the phase that failed
+    pub(crate) fn classify(error: anyhow::Error, kind: fn(anyhow::Error) -> TasjeError) -> Self {
+        match error.downcast::<TasjeError>() {
+            Ok(error) => error,
+            Err(error) => match error.downcast::<PackWarning>() {
+                Ok(warning) => TasjeError::Strict(warning),
+                Err(error) => kind(error),
+            },
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::app::App;
+use crate::environment::Platform;
+use crate::error::TasjeError;
+
+/// what electron apps usually need, see https://docs.flatpak.org/en/latest/electron.html
+static FINISH_ARGS: &[&str] = &[
+    "--share=ipc",
+    "--share=network",
+    "--socket=x11",
+    "--socket=pulseaudio",
+    "--device=dri",
+    "--talk-name=org.freedesktop.Notifications",
+];
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Manifest {
+    app_id: String,
+    runtime: &'static str,
+    runtime_version: &'static str,
+    sdk: &'static str,
+    base: &'static str,
+    base_version: &'static str,
+    command: String,
+    finish_args: Vec<&'static str>,
+    modules: Vec<Module>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Module {
+    name: String,
+    buildsystem: &'static str,
+    build_commands: Vec<String>,
+    sources: Vec<Source>,
+}
+
+#[derive(Serialize)]
+struct Source {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    path: &'static str,
+}
+
+/// a starting point for a flatpak manifest, which still needs the electron binary
+/// and a wrapper script for the command, so it's not meant to build as it is
+pub struct FlatpakGenerator {}
+
+impl FlatpakGenerator {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// electron-builder defaults to `com.electron.<name>` too
+    fn app_id(app: &App, platform: Platform) -> Result<String> {
+        match app.config().app_id(platform) {
+            Some(id) => Ok(id.to_string()),
+            None => Ok(format!("com.electron.{}", app.executable_name(platform)?)),
+        }
+    }
+
+    /// https://docs.flatpak.org/en/latest/manifests.html
+    pub fn generate(self, app: &App, platform: Platform) -> Result<String, TasjeError> {
+        self.generate_manifest(app, platform)
+            .map_err(TasjeError::Flatpak)
+    }
+
+    fn generate_manifest(self, app: &App, platform: Platform) -> Result<String> {
+        let app_id = FlatpakGenerator::app_id(app, platform)?;
+        let exec_name = app.executable_name(platform)?;
+        let desktop_name = app.desktop_name(platform)?;
+        let stem = desktop_name
+            .strip_suffix(".desktop")
+            .unwrap_or(&desktop_name);
+
+        // paths are relative to the output dir, where the manifest is written
+        let manifest = Manifest {
+            app_id: app_id.clone(),
+            runtime: "org.freedesktop.Platform",
+            runtime_version: "24.08",
+            sdk: "org.freedesktop.Sdk",
+            base: "org.electronjs.Electron2.BaseApp",
+            base_version: "24.08",
+            command: exec_name.clone(),
+            finish_args: FINISH_ARGS.to_vec(),
+            modules: vec![Module {
+                name: app.product_name(platform).to_string(),
+                buildsystem: "simple",
+                build_commands: vec![
+                    format!("mkdir -p /app/lib/{exec_name}"),
+                    format!("cp -r resources /app/lib/{exec_name}/"),
+                    format!(
+                        "install -Dm644 {desktop_name} /app/share/applications/{app_id}.desktop"
+                    ),
+                    format!(
+                        "install -Dm644 {stem}.metainfo.xml /app/share/metainfo/{app_id}.metainfo.xml"
+                    ),
+                    format!(
+                        "for size in $(cat icons/size-list); do install -Dm644 icons/$size.png /app/share/icons/hicolor/$size/apps/{app_id}.png; done"
+                    ),
+                ],
+                sources: vec![Source {
+                    kind: "dir",
+                    path: ".",
+                }],
+            }],
+        };
+
+        Ok(format!(
+            "# generated by tasje as a starting point, adjust before building\n{}",
+            serde_yaml::to_string(&manifest)?
+        ))
+    }
+
+    pub fn write_to_output_dir<P>(
+        self,
+        app: &App,
+        platform: Platform,
+        output: Option<P>,
+    ) -> Result<(), TasjeError>
+    where
+        P: AsRef<Path>,
+    {
+        let contents = self.generate(app, platform)?;
+        FlatpakGenerator::write_manifest(app, platform, output, contents)
+            .map_err(TasjeError::Flatpak)
+    }
+
+    fn write_manifest<P>(
+        app: &App,
+        platform: Platform,
+        output: Option<P>,
+        contents: String,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let file_name = format!("{}.yml", FlatpakGenerator::app_id(app, platform)?);
+        let mut target = app.output_dir(platform);
+        if let Some(out) = output {
+            target = target.join(out.as_ref());
+            if target.is_dir() {
+                target = target.join(file_name);
+            }
+        } else {
+            target = target.join(file_name);
+        }
+
+        // make sure dir exists
+        fs::create_dir_all(
+            target
+                .parent()
+                .ok_or_else(|| anyhow!("no flatpak manifest target parent"))?,
+        )?;
+        fs::write(target, contents)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FlatpakGenerator;
+    use crate::app::App;
+    use crate::environment::Platform;
+    use anyhow::Result;
+    use serde_json::json;
+
+    static LINUX: Platform = Platform::Linux;
+
+    #[test]
+    fn test_gen_flatpak() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "electron_tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "appId": "rocks.selfisekai.Tasje",
+                "productName": "Tasje",
+                "executableName": "tasje",
+            }),
+            "test_assets".into(),
+        )?;
+
+        let manifest = FlatpakGenerator::new().generate(&app, LINUX)?;
+        let parsed: serde_yaml::Value = serde_yaml::from_str(&manifest)?;
+        assert_eq!(parsed["app-id"], "rocks.selfisekai.Tasje");
+        assert_eq!(parsed["command"], "tasje");
+        assert_eq!(parsed["modules"][0]["name"], "Tasje");
+        assert!(manifest.contains("/app/share/applications/rocks.selfisekai.Tasje.desktop"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gen_flatpak_default_id() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({}),
+            "test_assets".into(),
+        )?;
+
+        let manifest = FlatpakGenerator::new().generate(&app, LINUX)?;
+        let parsed: serde_yaml::Value = serde_yaml::from_str(&manifest)?;
+        assert_eq!(parsed["app-id"], "com.electron.tasje");
+
+        Ok(())
+    }
+}
@@ -1,37 +1,115 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use once_cell::sync::Lazy;
 use regex::Regex;
-use std::collections::HashSet;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fs;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
+use crate::error::TasjeError;
+use crate::utils::write_atomically;
+
+/// sizes that svg icons are rasterized to, following the hicolor icon theme
+static SVG_SIZES: &[u32] = &[16, 24, 32, 48, 64, 128, 256, 512];
+
 static PNG_SIZE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\d+)x(\d+)\.png$").unwrap());
 
+/// used to pick the best source if multiple ones provide the same icon size.
+/// compares bits per pixel first, then the size of source data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct IconQuality {
+    bits_per_pixel: u32,
+    data_size: u64,
+}
+
 pub struct IconGenerator {
-    icon_sizes: HashSet<(u64, u64)>,
+    icon_sizes: HashMap<(u64, u64), IconQuality>,
+    svg_sizes: Vec<u32>,
+    cache_dir: Option<PathBuf>,
+    cache_hits: usize,
 }
 
 impl IconGenerator {
     pub fn new() -> Self {
         Self {
-            icon_sizes: HashSet::new(),
+            icon_sizes: HashMap::new(),
+            svg_sizes: SVG_SIZES.to_vec(),
+            cache_dir: None,
+            cache_hits: 0,
+        }
+    }
+
+    /// keep the optimized icons in `dir`, keyed by their contents before optimizing,
+    /// so that they are not optimized again in later builds
+    pub fn cache_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.cache_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// sizes to rasterize svg icons to, instead of the ones from the hicolor theme
+    pub fn svg_sizes(mut self, sizes: Vec<u32>) -> Self {
+        self.svg_sizes = sizes;
+        self
+    }
+
+    /// fails early if an icon file set for the target is in a format that icons
+    /// can not be generated from. files of other formats in icon directories are skipped
+    pub fn check_sources<P: AsRef<Path>>(icon_locations: &[P]) -> Result<(), TasjeError> {
+        for location in icon_locations {
+            let location = location.as_ref();
+            if location.is_file()
+                && source_format(location)
+                    .map_err(TasjeError::Icon)?
+                    .is_none()
+            {
+                return Err(TasjeError::Icon(anyhow!(
+                    "icon {location:?} is not in a supported format (png, ico, icns or svg)"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// whether the icon should be written, replacing a previous one of the same size if worse
+    fn consider_size(&mut self, size: (u64, u64), quality: IconQuality) -> bool {
+        match self.icon_sizes.get(&size) {
+            Some(existing) if *existing >= quality => false,
+            _ => {
+                self.icon_sizes.insert(size, quality);
+                true
+            }
         }
     }
 
-    pub fn generate<P1, P2>(mut self, icon_locations: Vec<P1>, icons_dir: P2) -> Result<()>
+    pub fn generate<P1, P2>(
+        mut self,
+        icon_locations: Vec<P1>,
+        icons_dir: P2,
+    ) -> Result<(), TasjeError>
     where
         P1: AsRef<Path>,
         P2: AsRef<Path>,
     {
-        let icons_dir = icons_dir.as_ref();
+        self.generate_icons(icon_locations, icons_dir.as_ref())
+            .map_err(TasjeError::Icon)
+    }
+
+    fn generate_icons<P>(&mut self, icon_locations: Vec<P>, icons_dir: &Path) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
         for location in icon_locations {
             let location = location.as_ref();
             self.handle_location(location, icons_dir)?;
         }
 
-        let mut sizes = self.icon_sizes.into_iter().collect::<Vec<_>>();
+        let mut sizes = self
+            .icon_sizes
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
         sizes.sort_by(|(w1, h1), (w2, h2)| w1.cmp(w2).then_with(|| h1.cmp(h2)));
         let sizes = sizes
             .into_iter()
@@ -56,25 +134,13 @@ impl IconGenerator {
     }
 
     fn handle_file(&mut self, location: &Path, icons_dir: &Path) -> Result<()> {
-        let mut file = fs::File::open(location)?;
-        let mut head = [0; 4];
-        file.read_exact(&mut head)?;
-
-        match &head {
-            b"icns" => {
-                self.handle_icns(location, icons_dir)?;
-            }
-            // ico
-            [0x00, 0x00, 0x01, 0x00] => {
-                self.handle_ico(location, icons_dir)?;
-            }
-            // png
-            [0x89, 0x50, 0x4e, 0x47] => {
-                self.handle_png(location, icons_dir)?;
-            }
-
+        match source_format(location)? {
+            Some(SourceFormat::Svg) => self.handle_svg(location, icons_dir)?,
+            Some(SourceFormat::Icns) => self.handle_icns(location, icons_dir)?,
+            Some(SourceFormat::Ico) => self.handle_ico(location, icons_dir)?,
+            Some(SourceFormat::Png) => self.handle_png(location, icons_dir)?,
             // unknown, ignore
-            _ => {}
+            None => {}
         }
 
         Ok(())
@@ -88,10 +154,11 @@ impl IconGenerator {
         .with_context(|| format!("on parsing ico icon: {ico_path:?}"))?;
         for entry in container.entries() {
             let (width, height) = (entry.width(), entry.height());
-            if self
-                .icon_sizes
-                .insert((width.into(), height.into()))
-            {
+            let quality = IconQuality {
+                bits_per_pixel: entry.bits_per_pixel().into(),
+                data_size: entry.data().len() as u64,
+            };
+            if self.consider_size((width.into(), height.into()), quality) {
                 let target_png = icons_dir.join(format!("{width}x{height}.png"));
                 entry
                     .decode()
@@ -118,10 +185,11 @@ impl IconGenerator {
                 .get_icon_with_type(icon_type)
                 .with_context(|| format!("on getting icns icon: {icon_type:?}, {icns_path:?}"))?;
             let (width, height) = (icon.width(), icon.height());
-            if self
-                .icon_sizes
-                .insert((width.into(), height.into()))
-            {
+            let quality = IconQuality {
+                bits_per_pixel: icon.pixel_format().bits_per_pixel(),
+                data_size: icon.data().len() as u64,
+            };
+            if self.consider_size((width.into(), height.into()), quality) {
                 let target_png = icons_dir.join(format!("{width}x{height}.png"));
                 icon.write_png(
                     fs::File::create(&target_png)
@@ -135,9 +203,46 @@ impl IconGenerator {
         Ok(())
     }
 
+    fn handle_svg(&mut self, svg_path: &Path, icons_dir: &Path) -> Result<()> {
+        let tree = resvg::usvg::Tree::from_data(
+            &fs::read(svg_path).with_context(|| format!("on reading svg icon: {svg_path:?}"))?,
+            &resvg::usvg::Options::default(),
+        )
+        .with_context(|| format!("on parsing svg icon: {svg_path:?}"))?;
+        // rasterized icons lose to any raster source of the same size,
+        // which was likely drawn for that size
+        let quality = IconQuality {
+            bits_per_pixel: 0,
+            data_size: 0,
+        };
+        for size in self.svg_sizes.clone() {
+            if !self.consider_size((size.into(), size.into()), quality) {
+                continue;
+            }
+            let mut pixmap = resvg::tiny_skia::Pixmap::new(size, size).unwrap();
+            resvg::render(
+                &tree,
+                resvg::tiny_skia::Transform::from_scale(
+                    size as f32 / tree.size().width(),
+                    size as f32 / tree.size().height(),
+                ),
+                &mut pixmap.as_mut(),
+            );
+            let target_png = icons_dir.join(format!("{size}x{size}.png"));
+            pixmap
+                .save_png(&target_png)
+                .with_context(|| format!("on writing png icon: {target_png:?}"))?;
+            self.optimize_png(target_png)?;
+        }
+
+        Ok(())
+    }
+
     fn handle_png(&mut self, png_path: &Path, icons_dir: &Path) -> Result<()> {
-        // this blindly trusts that the sizes in filename are correct
-        if let Some((width, height)) = png_path
+        let (header_size, quality) = png_header(png_path)?;
+        // this blindly trusts that the sizes in filename are correct,
+        // the header is only used for files without them, like `logo.png`
+        let (width, height) = png_path
             .file_name()
             .and_then(OsStr::to_str)
             .and_then(|filename| PNG_SIZE_REGEX.captures(filename))
@@ -147,19 +252,33 @@ impl IconGenerator {
                     c.get(2).unwrap().as_str().parse().unwrap(),
                 )
             })
-        {
-            if self.icon_sizes.insert((width, height)) {
-                let target_path = icons_dir.join(format!("{width}x{height}.png"));
-                fs::copy(png_path, &target_path)
-                    .with_context(|| format!("on copying png icon: {png_path:?}"))?;
-                self.optimize_png(target_path)?;
-            }
+            .unwrap_or(header_size);
+        if self.consider_size((width, height), quality) {
+            let target_path = icons_dir.join(format!("{width}x{height}.png"));
+            fs::copy(png_path, &target_path)
+                .with_context(|| format!("on copying png icon: {png_path:?}"))?;
+            self.optimize_png(target_path)?;
         }
 
         Ok(())
     }
 
-    fn optimize_png(&self, png_path: PathBuf) -> Result<()> {
+    fn optimize_png(&mut self, png_path: PathBuf) -> Result<()> {
+        let cached = match &self.cache_dir {
+            Some(cache_dir) => {
+                let contents = fs::read(&png_path)
+                    .with_context(|| format!("on reading png icon: {png_path:?}"))?;
+                Some(cache_dir.join(format!("{:x}.png", Sha256::digest(contents))))
+            }
+            None => None,
+        };
+        if let Some(cached) = cached.as_ref().filter(|c| c.is_file()) {
+            fs::copy(cached, &png_path)
+                .with_context(|| format!("on copying cached png icon: {cached:?}"))?;
+            self.cache_hits += 1;
+            return Ok(());
+        }
+
         oxipng::optimize(
             &oxipng::InFile::Path(png_path.clone()),
             &oxipng::OutFile::Path {
@@ -173,16 +292,77 @@ impl IconGenerator {
         )
         .with_context(|| format!("on optimizing png icon: {png_path:?}"))?;
 
+        if let Some(cached) = cached {
+            fs::create_dir_all(cached.parent().unwrap())?;
+            let optimized = fs::read(&png_path)?;
+            // other builds might be using the same cache at the same time
+            write_atomically(&cached, |file| Ok(file.write_all(&optimized)?))
+                .with_context(|| format!("on caching png icon: {cached:?}"))?;
+        }
+
         Ok(())
     }
 }
 
+enum SourceFormat {
+    Svg,
+    Icns,
+    Ico,
+    Png,
+}
+
+/// recognizes the icon formats by their magic bytes, or `None` if unknown
+fn source_format(location: &Path) -> Result<Option<SourceFormat>> {
+    // svg is text, so there are no magic bytes to check
+    if location.extension() == Some(OsStr::new("svg")) {
+        return Ok(Some(SourceFormat::Svg));
+    }
+    let mut file = fs::File::open(location)?;
+    let mut head = [0; 4];
+    file.read_exact(&mut head)?;
+
+    Ok(match &head {
+        b"icns" => Some(SourceFormat::Icns),
+        [0x00, 0x00, 0x01, 0x00] => Some(SourceFormat::Ico),
+        [0x89, 0x50, 0x4e, 0x47] => Some(SourceFormat::Png),
+        _ => None,
+    })
+}
+
+/// dimensions and quality, from the IHDR chunk
+fn png_header(png_path: &Path) -> Result<((u64, u64), IconQuality)> {
+    let mut file =
+        fs::File::open(png_path).with_context(|| format!("on opening png icon: {png_path:?}"))?;
+    // signature (8), IHDR length and type (8), width and height (8), bit depth, color type
+    let mut head = [0; 26];
+    file.read_exact(&mut head)
+        .with_context(|| format!("on reading png header: {png_path:?}"))?;
+    let width = u32::from_be_bytes(head[16..20].try_into().unwrap());
+    let height = u32::from_be_bytes(head[20..24].try_into().unwrap());
+    let channels = match head[25] {
+        // grayscale with alpha
+        4 => 2,
+        // truecolor
+        2 => 3,
+        // truecolor with alpha
+        6 => 4,
+        // grayscale, indexed
+        _ => 1,
+    };
+    let quality = IconQuality {
+        bits_per_pixel: u32::from(head[24]) * channels,
+        data_size: file.metadata()?.len(),
+    };
+    Ok(((width.into(), height.into()), quality))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::IconGenerator;
+    use super::{png_header, IconGenerator};
     use crate::app::App;
+    use crate::environment::Platform;
     use anyhow::Result;
-    use std::fs::{create_dir_all, read_to_string};
+    use std::fs::{create_dir_all, read, read_to_string, remove_dir_all, write};
     use std::path::Path;
 
     #[test]
@@ -190,7 +370,7 @@ mod tests {
         let icons_dir = Path::new(".test-workspace/icons_linux");
         create_dir_all(icons_dir)?;
         let app = App::new_from_package_file("test_assets/package.json")?;
-        IconGenerator::new().generate(app.icon_locations(), icons_dir)?;
+        IconGenerator::new().generate(app.icon_locations(Platform::Linux), icons_dir)?;
         assert_eq!(
             read_to_string(icons_dir.join("size-list"))?,
             "10x10
@@ -208,7 +388,7 @@ mod tests {
         let icons_dir = Path::new(".test-workspace/icons_win");
         create_dir_all(icons_dir)?;
         let app = App::new_from_package_file("test_assets/package-win.json")?;
-        IconGenerator::new().generate(app.icon_locations(), icons_dir)?;
+        IconGenerator::new().generate(app.icon_locations(Platform::Windows), icons_dir)?;
         assert_eq!(read_to_string(icons_dir.join("size-list"))?, "32x32");
         assert!(icons_dir.join("32x32.png").is_file());
         Ok(())
@@ -219,7 +399,7 @@ mod tests {
         let icons_dir = Path::new(".test-workspace/icons_mac");
         create_dir_all(icons_dir)?;
         let app = App::new_from_package_file("test_assets/package-mac.json")?;
-        IconGenerator::new().generate(app.icon_locations(), icons_dir)?;
+        IconGenerator::new().generate(app.icon_locations(Platform::Darwin), icons_dir)?;
         assert_eq!(
             read_to_string(icons_dir.join("size-list"))?,
             "128x128
@@ -237,7 +417,7 @@ mod tests {
         let icons_dir = Path::new(".test-workspace/icons_mac");
         create_dir_all(icons_dir)?;
         let app = App::new_from_package_file("test_assets/package-build_resources.json")?;
-        IconGenerator::new().generate(app.icon_locations(), icons_dir)?;
+        IconGenerator::new().generate(app.icon_locations(Platform::Darwin), icons_dir)?;
         assert_eq!(
             read_to_string(icons_dir.join("size-list"))?,
             "128x128
@@ -249,4 +429,176 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_duplicate_sizes() -> Result<()> {
+        let linux = Path::new("test_assets/icons_linux");
+        let mac = Path::new("test_assets/icons_mac/icon.icns");
+        let icons_dir_1 = Path::new(".test-workspace/icons_duplicate_1");
+        let icons_dir_2 = Path::new(".test-workspace/icons_duplicate_2");
+        create_dir_all(icons_dir_1)?;
+        create_dir_all(icons_dir_2)?;
+        IconGenerator::new().generate(vec![linux, mac], icons_dir_1)?;
+        IconGenerator::new().generate(vec![mac, linux], icons_dir_2)?;
+        for name in ["128x128.png", "256x256.png"] {
+            assert_eq!(read(icons_dir_1.join(name))?, read(icons_dir_2.join(name))?);
+        }
+        assert_eq!(
+            read_to_string(icons_dir_1.join("size-list"))?,
+            "10x10
+128x128
+256x256
+512x512"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_svg() -> Result<()> {
+        let app = App::new_from_package_file("test_assets/package-svg.json")?;
+
+        let icons_dir = Path::new(".test-workspace/icons_svg_linux");
+        create_dir_all(icons_dir)?;
+        IconGenerator::new().generate(app.icon_locations(Platform::Linux), icons_dir)?;
+        assert_eq!(
+            read_to_string(icons_dir.join("size-list"))?,
+            "16x16
+24x24
+32x32
+48x48
+64x64
+128x128
+256x256
+512x512"
+        );
+        let (size, _) = png_header(&icons_dir.join("512x512.png"))?;
+        assert_eq!(size, (512, 512));
+
+        let icons_dir = Path::new(".test-workspace/icons_svg_win");
+        create_dir_all(icons_dir)?;
+        IconGenerator::new().generate(app.icon_locations(Platform::Windows), icons_dir)?;
+        assert_eq!(read_to_string(icons_dir.join("size-list"))?, "32x32");
+        Ok(())
+    }
+
+    #[test]
+    fn test_svg_sizes() -> Result<()> {
+        let app = App::from_values(
+            serde_json::json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            serde_json::json!({
+                "icon": "icons_svg/icon.svg",
+                "linux": {
+                    "iconSizes": [48, 16, 256],
+                },
+            }),
+            "test_assets".into(),
+        )?;
+        let icons_dir = Path::new(".test-workspace/icons_svg_sizes");
+        if icons_dir.exists() {
+            remove_dir_all(icons_dir)?;
+        }
+        create_dir_all(icons_dir)?;
+        IconGenerator::new()
+            .svg_sizes(app.config().icon_sizes(Platform::Linux)?.to_vec())
+            .generate(app.icon_locations(Platform::Linux), icons_dir)?;
+        assert_eq!(
+            read_to_string(icons_dir.join("size-list"))?,
+            "16x16
+48x48
+256x256"
+        );
+
+        let app = App::from_values(
+            serde_json::json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            serde_json::json!({
+                "linux": {
+                    "iconSizes": [0, 16],
+                },
+            }),
+            "test_assets".into(),
+        )?;
+        assert!(app.config().icon_sizes(Platform::Linux).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_svg_with_raster() -> Result<()> {
+        let svg = "test_assets/icons_svg/icon.svg";
+        // 24 bits per pixel, which still ranks above rasterized svgs, as those get the lowest quality
+        let png = "test_assets/icons_linux/128x128.png";
+        let raster_dir = Path::new(".test-workspace/icons_svg_raster_only");
+        create_dir_all(raster_dir)?;
+        IconGenerator::new().generate(vec![png], raster_dir)?;
+        for (name, locations) in [("svg_first", [svg, png]), ("png_first", [png, svg])] {
+            let icons_dir = Path::new(".test-workspace").join(format!("icons_svg_raster_{name}"));
+            create_dir_all(&icons_dir)?;
+            IconGenerator::new().generate(locations.to_vec(), &icons_dir)?;
+            assert_eq!(
+                read(icons_dir.join("128x128.png"))?,
+                read(raster_dir.join("128x128.png"))?,
+                "{name}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsized_png() -> Result<()> {
+        let icons_dir = Path::new(".test-workspace/icons_unsized");
+        create_dir_all(icons_dir)?;
+        IconGenerator::new().generate(vec!["test_assets/icons_unsized/logo.png"], icons_dir)?;
+        assert_eq!(read_to_string(icons_dir.join("size-list"))?, "10x10");
+        assert!(icons_dir.join("10x10.png").is_file());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_sources() -> Result<()> {
+        let app = App::new_from_package_file("test_assets/package-win.json")?;
+        IconGenerator::check_sources(&app.icon_locations(Platform::Windows))?;
+        // directories may have other files in them
+        IconGenerator::check_sources(&["test_assets/icons_linux"])?;
+
+        let sources_dir = Path::new(".test-workspace/icons_check_sources");
+        create_dir_all(sources_dir)?;
+        let bmp = sources_dir.join("icon.bmp");
+        write(&bmp, b"BM\0\0\0\0")?;
+        assert_eq!(
+            IconGenerator::check_sources(&[&bmp])
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "on generating icons: icon {bmp:?} is not in a supported format (png, ico, icns or svg)"
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache() -> Result<()> {
+        let icons_dir = Path::new(".test-workspace/icons_cache");
+        let cache_dir = Path::new(".test-workspace/icons_cache_store");
+        create_dir_all(icons_dir)?;
+        if cache_dir.exists() {
+            remove_dir_all(cache_dir)?;
+        }
+        let locations = vec!["test_assets/icons_mac/icon.icns"];
+
+        let mut first = IconGenerator::new().cache_dir(cache_dir);
+        first.generate_icons(locations.clone(), icons_dir)?;
+        assert_eq!(first.cache_hits, 0);
+        let optimized = read(icons_dir.join("512x512.png"))?;
+
+        let mut second = IconGenerator::new().cache_dir(cache_dir);
+        second.generate_icons(locations, icons_dir)?;
+        assert_eq!(second.cache_hits, 3);
+        assert_eq!(read(icons_dir.join("512x512.png"))?, optimized);
+        Ok(())
+    }
 }
@@ -2,7 +2,10 @@ pub mod app;
 pub mod config;
 pub mod desktop;
 pub mod environment;
+pub mod error;
+pub mod flatpak;
 mod icons;
+pub mod metainfo;
 pub mod pack;
 pub mod package;
 pub mod utils;
@@ -0,0 +1,300 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::app::App;
+use crate::environment::Platform;
+use crate::error::TasjeError;
+
+/// releases listed by default, as older ones are rarely shown by software centers
+const DEFAULT_MAX_RELEASES: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct MetainfoGenerator {
+    lines: Vec<String>,
+    changelog: Option<PathBuf>,
+    max_releases: usize,
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// versions and dates of the releases from a changelog in the keep-a-changelog format,
+/// like `## [1.2.0] - 2024-01-15`, newest first. unreleased changes are skipped
+fn changelog_releases(changelog: &str) -> Vec<(&str, Option<&str>)> {
+    changelog
+        .lines()
+        .filter_map(|line| line.strip_prefix("## "))
+        .filter_map(|heading| {
+            let (version, date) = match heading.split_once(" - ") {
+                Some((version, date)) => (version, Some(date.trim())),
+                None => (heading, None),
+            };
+            let version = version
+                .trim()
+                .trim_start_matches('[')
+                .trim_end_matches(']');
+            // headings of other sections, like "Unreleased", don't start with a number
+            version
+                .starts_with(|ch: char| ch.is_ascii_digit())
+                .then_some((version, date))
+        })
+        .collect()
+}
+
+/// npm allows some values that are not SPDX expressions
+/// https://docs.npmjs.com/cli/configuring-npm/package-json#license
+fn spdx_license(license: &str) -> Option<&str> {
+    match license {
+        "UNLICENSED" => Some("LicenseRef-proprietary"),
+        l if l.starts_with("SEE LICENSE IN") => None,
+        l => Some(l),
+    }
+}
+
+impl MetainfoGenerator {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            changelog: None,
+            max_releases: DEFAULT_MAX_RELEASES,
+        }
+    }
+
+    /// list releases from this CHANGELOG.md, relative to the project root
+    pub fn changelog<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.changelog = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// how many of the most recent releases from the changelog to list
+    pub fn max_releases(mut self, count: usize) -> Self {
+        self.max_releases = count;
+        self
+    }
+
+    fn add_element<V: AsRef<str>>(&mut self, name: &str, val: V) {
+        self.lines
+            .push(format!("  <{name}>{}</{name}>", xml_escape(val.as_ref())));
+    }
+
+    /// https://www.freedesktop.org/software/appstream/docs/chap-Quickstart.html
+    pub fn generate(self, app: &App, platform: Platform) -> Result<String, TasjeError> {
+        self.generate_metainfo(app, platform)
+            .map_err(TasjeError::Metainfo)
+    }
+
+    fn generate_metainfo(mut self, app: &App, platform: Platform) -> Result<String> {
+        let desktop_name = app.desktop_name(platform)?;
+        let id = desktop_name
+            .strip_suffix(".desktop")
+            .unwrap_or(&desktop_name);
+
+        self.add_element("id", id);
+        self.add_element("metadata_license", "CC0-1.0");
+        if let Some(license) = app.license().and_then(spdx_license) {
+            self.add_element("project_license", license);
+        }
+        self.add_element("name", app.product_name(platform));
+        if let Some(summary) = app.description(platform) {
+            self.add_element("summary", summary);
+        }
+        if let Some(name) = app.author().and_then(|author| author.name()) {
+            self.add_element("developer_name", name);
+        }
+        self.lines.push(format!(
+            "  <launchable type=\"desktop-id\">{}</launchable>",
+            xml_escape(&desktop_name)
+        ));
+        if let Some(changelog) = &self.changelog {
+            let changelog = fs::read_to_string(app.root.join(changelog))
+                .with_context(|| format!("on reading the changelog: {changelog:?}"))?;
+            let releases = changelog_releases(&changelog);
+            if !releases.is_empty() && self.max_releases > 0 {
+                self.lines.push("  <releases>".to_string());
+                for (version, date) in releases.into_iter().take(self.max_releases) {
+                    let date = date
+                        .map(|d| format!(" date=\"{}\"", xml_escape(d)))
+                        .unwrap_or_default();
+                    self.lines.push(format!(
+                        "    <release version=\"{}\"{date}/>",
+                        xml_escape(version)
+                    ));
+                }
+                self.lines.push("  </releases>".to_string());
+            }
+        }
+
+        let mut contents = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        if let Some(copyright) = app.copyright(platform)? {
+            // "--" is not allowed inside of xml comments
+            contents.push_str(&format!("<!-- {} -->\n", copyright.replace("--", "- -")));
+        }
+        contents.push_str("<component type=\"desktop-application\">\n");
+        for line in self.lines {
+            contents.push_str(&line);
+            contents.push('\n');
+        }
+        contents.push_str("</component>\n");
+
+        Ok(contents)
+    }
+
+    pub fn write_to_output_dir<P>(
+        self,
+        app: &App,
+        platform: Platform,
+        output: Option<P>,
+    ) -> Result<(), TasjeError>
+    where
+        P: AsRef<Path>,
+    {
+        let contents = self.generate(app, platform)?;
+        Self::write_metainfo(app, platform, output, contents).map_err(TasjeError::Metainfo)
+    }
+
+    fn write_metainfo<P>(
+        app: &App,
+        platform: Platform,
+        output: Option<P>,
+        contents: String,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let desktop_name = app.desktop_name(platform)?;
+        let file_name = format!(
+            "{}.metainfo.xml",
+            desktop_name
+                .strip_suffix(".desktop")
+                .unwrap_or(&desktop_name)
+        );
+        let mut target = app.output_dir(platform);
+        if let Some(out) = output {
+            target = target.join(out.as_ref());
+            if target.is_dir() {
+                target = target.join(file_name);
+            }
+        } else {
+            target = target.join(file_name);
+        }
+
+        // make sure dir exists
+        fs::create_dir_all(
+            target
+                .parent()
+                .ok_or_else(|| anyhow!("no metainfo target parent"))?,
+        )?;
+        fs::write(target, contents)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changelog_releases, MetainfoGenerator};
+    use crate::app::App;
+    use crate::environment::Platform;
+    use anyhow::Result;
+    use serde_json::json;
+    use std::env::current_dir;
+    use std::fs;
+
+    static LINUX: Platform = Platform::Linux;
+
+    #[test]
+    fn test_gen_metainfo() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "electron_tasje",
+                "version": "1.0.0",
+                "description": "Packs <Electron> apps",
+                "license": "MIT",
+                "author": "lauren n. liberda <lauren@selfisekai.rocks>",
+            }),
+            json!({
+                "productName": "Tasje",
+                "copyright": "Copyright © 2022 lauren n. liberda",
+            }),
+            "test_assets".into(),
+        )?;
+
+        assert_eq!(
+            MetainfoGenerator::new().generate(&app, LINUX)?,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Copyright © 2022 lauren n. liberda -->
+<component type="desktop-application">
+  <id>electron_tasje</id>
+  <metadata_license>CC0-1.0</metadata_license>
+  <project_license>MIT</project_license>
+  <name>Tasje</name>
+  <summary>Packs &lt;Electron&gt; apps</summary>
+  <developer_name>lauren n. liberda</developer_name>
+  <launchable type="desktop-id">electron_tasje.desktop</launchable>
+</component>
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gen_metainfo_unlicensed() -> Result<()> {
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "license": "UNLICENSED",
+            }),
+            json!({}),
+            "test_assets".into(),
+        )?;
+
+        let metainfo = MetainfoGenerator::new().generate(&app, LINUX)?;
+        assert!(metainfo.contains("<project_license>LicenseRef-proprietary</project_license>"));
+        assert!(!metainfo.contains("<!--"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_changelog_releases() -> Result<()> {
+        let changelog = "# Changelog\n\n## [Unreleased]\n\n- wip\n\n## [1.2.0] - 2024-01-15\n\n### Added\n\n- things\n\n## [1.1.0] - 2023-12-01\n\n## 1.0.0\n";
+        assert_eq!(
+            changelog_releases(changelog),
+            [
+                ("1.2.0", Some("2024-01-15")),
+                ("1.1.0", Some("2023-12-01")),
+                ("1.0.0", None),
+            ]
+        );
+
+        let root = current_dir()?.join(".test-workspace/metainfo_changelog");
+        fs::create_dir_all(&root)?;
+        fs::write(root.join("CHANGELOG.md"), changelog)?;
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.2.0",
+            }),
+            json!({}),
+            root,
+        )?;
+        let metainfo = MetainfoGenerator::new()
+            .changelog("CHANGELOG.md")
+            .max_releases(2)
+            .generate(&app, LINUX)?;
+        assert!(metainfo.contains(
+            "  <releases>\n    <release version=\"1.2.0\" date=\"2024-01-15\"/>\n    <release version=\"1.1.0\" date=\"2023-12-01\"/>\n  </releases>\n"
+        ));
+        assert!(!metainfo.contains("1.0.0"));
+
+        Ok(())
+    }
+}
@@ -1,19 +1,48 @@
 use crate::app::App;
-use crate::config::CopyDef;
-use crate::desktop::DesktopGenerator;
+use crate::config::{preset_files, CopyDef};
+use crate::desktop::{empty_associations, inferred_mime_types, nonstandard_keys, DesktopGenerator};
 use crate::environment::{Environment, Platform, HOST_ENVIRONMENT};
+use crate::error::TasjeError;
 use crate::icons::IconGenerator;
-use crate::walker::Walker;
-use anyhow::Result;
+use crate::metainfo::MetainfoGenerator;
+use crate::utils::{
+    expand_home, hidden_sibling, replace_dir, same_contents, write_atomically, DirectoryCreator,
+};
+use crate::walker::{export_ignore_globs, unpack_matcher, Walker};
+use anyhow::{anyhow, bail, Context, Result};
 use asar::AsarWriter;
 use once_cell::sync::Lazy;
-use std::fs::{self, read, File};
-use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::fs::{self, read};
+use std::io::{self, ErrorKind, Write};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
 
 static ROOT: Lazy<PathBuf> = Lazy::new(|| PathBuf::from("/"));
 
+pub(crate) static OUTPUT_PLACEHOLDER: &str = "${output}";
+
 static NODE_MODULES_GLOB: Lazy<CopyDef> =
-    Lazy::new(|| CopyDef::Simple("node_modules/**/*".to_string()));
+    Lazy::new(|| CopyDef::Simple("/node_modules/**/*".to_string()));
+
+static LOCKFILES_FILTER: &str = "!**/{package-lock.json,yarn.lock}";
+
+/// source maps make the asar bigger, and can leak the original sources
+static SOURCEMAPS_FILTER: &str = "!**/*.map";
+
+static DOTFILES_FILTERS: Lazy<Vec<CopyDef>> = Lazy::new(|| {
+    ["!**/.*", "!**/.*/**"]
+        .into_iter()
+        .map(|glob| CopyDef::Simple(glob.to_string()))
+        .collect()
+});
 
 static FORCED_FILTERS: Lazy<Vec<CopyDef>> = Lazy::new(|| {
     [
@@ -25,7 +54,8 @@ static FORCED_FILTERS: Lazy<Vec<CopyDef>> = Lazy::new(|| {
         "!**/{.editorconfig,.DS_Store,.git,.svn,.hg,CVS,RCS,.gitattributes,.nvmrc,.nycrc,Makefile,CMakeLists.txt}",
         "!**/{__pycache__,thumbs.db,.flowconfig,.idea,.vs,.vscode,.nyc_output,.docker-compose.yml}",
         "!**/{.github,.gitlab,.gitlab-ci.yml,appveyor.yml,.travis.yml,circle.yml,.woodpecker.yml}",
-        "!**/{package-lock.json,yarn.lock}",
+        LOCKFILES_FILTER,
+        SOURCEMAPS_FILTER,
         "!**/.{git,eslint,tslint,prettier,docker,npm,yarn}ignore",
         "!**/.{prettier,eslint,jshint,jsdoc}rc",
         "!**/{.prettierrc,webpack.config,.jshintrc,jsdoc,.eslintrc,tsconfig}{,.json,.js,.yml,yaml}",
@@ -35,32 +65,298 @@ static FORCED_FILTERS: Lazy<Vec<CopyDef>> = Lazy::new(|| {
     ].into_iter().map(str::to_string).map(CopyDef::Simple).collect()
 });
 
+/// 2 GiB, the maximum size of a buffer in node.js on 32-bit platforms
+const DEFAULT_MAX_ASAR_FILE_SIZE: u64 = 2 << 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AsarLimits {
+    pub max_files: Option<usize>,
+    pub max_file_size: Option<u64>,
+    /// fail instead of warning when a limit is exceeded
+    pub strict: bool,
+}
+
+impl Default for AsarLimits {
+    fn default() -> Self {
+        AsarLimits {
+            max_files: None,
+            max_file_size: Some(DEFAULT_MAX_ASAR_FILE_SIZE),
+            strict: false,
+        }
+    }
+}
+
+/// renames paths inside of the asar, like flattening `node_modules/@scope/pkg`
+/// to `node_modules/pkg`. `replacement` can refer to the captures, like `$1`
+#[derive(Clone, Debug)]
+pub struct PathRewrite {
+    pub pattern: Regex,
+    pub replacement: String,
+}
+
+impl PathRewrite {
+    /// paths are matched with `/` as the separator, without the leading slash
+    fn apply(&self, dest: &Path) -> Option<PathBuf> {
+        let path = asar_path(dest);
+        self.pattern.is_match(&path).then(|| {
+            PathBuf::from(
+                self.pattern
+                    .replace(&path, &self.replacement)
+                    .as_ref(),
+            )
+        })
+    }
+}
+
+#[non_exhaustive]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PackWarning {
+    #[error("asar holds {count} files, more than the limit of {limit}")]
+    TooManyAsarFiles { count: usize, limit: usize },
+    #[error("{path:?} has {size} bytes, more than the asar file size limit of {limit}")]
+    AsarFileTooLarge {
+        path: PathBuf,
+        size: u64,
+        limit: u64,
+    },
+    #[error("entry point {main:?} from package.json is not packed into the asar")]
+    MissingMain { main: String },
+    #[error("extraMetadata is not an object, so it is ignored")]
+    InvalidExtraMetadata,
+    #[error("asar is disabled, so the asarUnpack patterns are ignored: {patterns:?}")]
+    AsarUnpackIgnored { patterns: Vec<String> },
+    #[error("{from:?} from a file set does not exist, so nothing is copied from it")]
+    MissingFrom { from: PathBuf },
+    #[error("file set with \"to\": {to:?} has no \"from\" and no filters, so it is skipped instead of copying everything")]
+    UnboundedFileSet { to: String },
+    #[error("{key:?} is defined more than once in the config file, only the last value is used")]
+    DuplicateConfigKey { key: String },
+    #[error(
+        "{key:?} in the desktop config is not a standard key, custom keys should start with \"X-\""
+    )]
+    NonstandardDesktopKey { key: String },
+    #[error("{key:?} is empty, so the association does nothing")]
+    EmptyAssociation { key: String },
+    #[error("file association for {ext:?} has no mimeType, so {mime_type:?} is assumed")]
+    InferredMimeType { ext: String, mime_type: String },
+    #[error("output dir {dir:?} is matched by the files globs, so it is left out. add \"!/{}\" to files to skip it", dir.display())]
+    OutputDirMatched { dir: PathBuf },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PackSummary {
+    /// files packed into the asar, including package.json
+    pub asar_files: usize,
+    /// files copied by extraFiles and extraResources
+    pub extra_files: usize,
+    /// extra files not copied, because they already existed with the same contents
+    pub skipped_extra_files: usize,
+    pub warnings: Vec<PackWarning>,
+    /// `process.version` of node.js, if it was run to evaluate a js config
+    pub node_version: Option<String>,
+    pub timings: PhaseTimings,
+    pub copy_lists: CopyLists,
+}
+
+impl PackSummary {
+    /// the biggest files packed into the asar, biggest first, with their paths in the asar.
+    /// for finding what makes the asar big
+    pub fn largest_asar_files(&self, count: usize) -> io::Result<Vec<(PathBuf, u64)>> {
+        let mut sizes = self
+            .copy_lists
+            .asar
+            .iter()
+            .map(|(source, dest)| Ok((dest.clone(), fs::metadata(source)?.len())))
+            .collect::<io::Result<Vec<_>>>()?;
+        sizes.sort_by(|(a_path, a_size), (b_path, b_size)| {
+            b_size
+                .cmp(a_size)
+                .then_with(|| a_path.cmp(b_path))
+        });
+        sizes.truncate(count);
+        Ok(sizes)
+    }
+}
+
+/// sources and destinations of the copied files, for debugging the globs.
+/// filled in on dry runs, too
+#[derive(Debug, Clone, Default)]
+pub struct CopyLists {
+    /// files of the app, with paths relative to the asar root (or `resources/app`)
+    pub asar: Vec<(PathBuf, PathBuf)>,
+    /// files also copied to `app.asar.unpacked`
+    pub unpacked: Vec<(PathBuf, PathBuf)>,
+    /// extraFiles and extraResources, with full destination paths
+    pub extra: Vec<(PathBuf, PathBuf)>,
+}
+
+/// packed files that changed between two builds, compared by their `app.asar.filelist`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileListDiff {
+    pub added: Vec<(String, u64)>,
+    pub removed: Vec<(String, u64)>,
+    /// with the old and the new size
+    pub resized: Vec<(String, u64, u64)>,
+}
+
+impl FileListDiff {
+    pub fn new(old: &BTreeMap<String, u64>, new: &BTreeMap<String, u64>) -> Self {
+        let mut diff = FileListDiff::default();
+        for (path, &size) in new {
+            match old.get(path) {
+                None => diff.added.push((path.clone(), size)),
+                Some(&old_size) if old_size != size => {
+                    diff.resized.push((path.clone(), old_size, size))
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .iter()
+            .filter(|(path, _)| !new.contains_key(*path))
+            .map(|(path, &size)| (path.clone(), size))
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.resized.is_empty()
+    }
+}
+
+/// reads an `app.asar.filelist`, with a path and a size in each line
+pub fn parse_asar_filelist(contents: &str) -> Result<BTreeMap<String, u64>> {
+    contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            // paths may contain tabs, but the sizes don't
+            let (path, size) = line
+                .rsplit_once('\t')
+                .ok_or_else(|| anyhow!("no size in the filelist line {line:?}"))?;
+            let size = size
+                .parse()
+                .with_context(|| format!("invalid size in the filelist line {line:?}"))?;
+            Ok((path.to_string(), size))
+        })
+        .collect()
+}
+
+/// wall-clock time spent in each phase of packing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseTimings {
+    /// finding the files to pack into the asar
+    pub walk: Duration,
+    /// reading the files and writing the asar
+    pub asar_write: Duration,
+    /// copying the files unpacked from the asar
+    pub unpack_copy: Duration,
+    /// finding and copying extraFiles and extraResources
+    pub extra_copy: Duration,
+    /// generating the desktop entry and metainfo
+    pub desktop: Duration,
+    pub icons: Duration,
+}
+
+impl PhaseTimings {
+    pub fn total(&self) -> Duration {
+        self.walk + self.asar_write + self.unpack_copy + self.extra_copy + self.desktop + self.icons
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct PackingProcessBuilder {
-    app: App,
+    app: Arc<App>,
     base_output_dir: Option<PathBuf>,
     icons_output_dir: Option<PathBuf>,
     resources_output_dir: Option<PathBuf>,
+    extra_files_output_dir: Option<PathBuf>,
+    extra_resources_output_dir: Option<PathBuf>,
+    unpack_output_dir: Option<PathBuf>,
     target_environment: Option<Environment>,
     additional_files: Vec<CopyDef>,
     additional_asar_unpack: Vec<String>,
     additional_extra_resources: Vec<CopyDef>,
+    reincluded_files: Vec<CopyDef>,
+    excluded_files: Vec<CopyDef>,
+    presets: Vec<String>,
+    desktop_generator: Option<DesktopGenerator>,
+    metainfo_generator: Option<MetainfoGenerator>,
+    node_modules: bool,
+    resources: bool,
+    native_unpack_radius: Option<usize>,
+    follow_symlinks: bool,
+    asar_limits: AsarLimits,
+    dry_run: bool,
+    strict_main: bool,
+    strict_from: bool,
+    strict_extra_metadata: bool,
+    strict_associations: bool,
+    icons: bool,
+    icon_cache_dir: Option<PathBuf>,
+    jobs: Option<usize>,
+    asar_filelist: bool,
+    overwrite_dest: bool,
+    export_ignore: bool,
+    path_rewrites: Vec<PathRewrite>,
+    read_batch_size: Option<u64>,
+    include_sourcemaps: bool,
+    dotfiles: bool,
+    standard_filters: bool,
+    atomic_output: bool,
 }
 
 impl PackingProcessBuilder {
-    pub fn new(app: App) -> Self {
+    /// the app can be shared with other builders, like when packing for multiple targets
+    pub fn new<A: Into<Arc<App>>>(app: A) -> Self {
+        let app = app.into();
+        let standard_filters = app
+            .tasje_options()
+            .standard_filters
+            .unwrap_or(true);
         PackingProcessBuilder {
             app,
             base_output_dir: None,
             icons_output_dir: None,
             resources_output_dir: None,
+            extra_files_output_dir: None,
+            extra_resources_output_dir: None,
+            unpack_output_dir: None,
             target_environment: None,
             additional_files: Vec::new(),
             additional_asar_unpack: Vec::new(),
             additional_extra_resources: Vec::new(),
+            reincluded_files: Vec::new(),
+            excluded_files: Vec::new(),
+            presets: Vec::new(),
+            desktop_generator: None,
+            metainfo_generator: None,
+            node_modules: true,
+            resources: true,
+            native_unpack_radius: None,
+            follow_symlinks: true,
+            asar_limits: AsarLimits::default(),
+            dry_run: false,
+            strict_main: false,
+            strict_from: false,
+            strict_extra_metadata: false,
+            strict_associations: false,
+            icons: true,
+            icon_cache_dir: None,
+            jobs: None,
+            asar_filelist: false,
+            overwrite_dest: true,
+            export_ignore: false,
+            path_rewrites: Vec::new(),
+            read_batch_size: None,
+            include_sourcemaps: false,
+            dotfiles: true,
+            standard_filters,
+            atomic_output: false,
         }
     }
 
+    /// relative to the project root, unless it's absolute
     pub fn base_output_dir<P>(mut self, path: P) -> Self
     where
         P: AsRef<Path>,
@@ -69,102 +365,644 @@ impl PackingProcessBuilder {
         self
     }
 
+    /// where "extraFiles" are copied to, relative to the base output dir. defaults to it
+    pub fn extra_files_output_dir<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.extra_files_output_dir = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// where "extraResources" are copied to, relative to the base output dir.
+    /// defaults to the resources dir, next to the asar
+    pub fn extra_resources_output_dir<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.extra_resources_output_dir = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// where the files unpacked from the asar are copied to, relative to the base output dir.
+    /// defaults to `app.asar.unpacked` next to the asar, which is the only place electron
+    /// looks for them, so files unpacked elsewhere have to be moved or linked there.
+    /// the files are also packed into the asar as usual, which does not record their location.
+    /// packing fails if the dir is not inside the base output dir
+    pub fn unpack_output_dir<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.unpack_output_dir = Some(path.as_ref().to_path_buf());
+        self
+    }
+
     pub fn target_environment(mut self, env: Environment) -> Self {
         self.target_environment = Some(env);
         self
     }
 
+    /// globs packed in addition to "files" from the config. these are evaluated after
+    /// the built-in filters, so explicitly added files are never excluded by them
     pub fn additional_files(mut self, add: Vec<CopyDef>) -> Self {
         self.additional_files.extend(add);
         self
     }
 
+    /// merged with "asarUnpack" from the config
     pub fn additional_asar_unpack(mut self, add: Vec<String>) -> Self {
         self.additional_asar_unpack.extend(add);
         self
     }
 
+    /// merged with "extraResources" from the config
     pub fn additional_extra_resources(mut self, add: Vec<CopyDef>) -> Self {
         self.additional_extra_resources.extend(add);
         self
     }
 
-    pub fn build(self) -> PackingProcess {
-        let environment = self
+    /// globs of files to pack even if they are excluded by the built-in filters,
+    /// like `node_modules/foo/data.md`. these take precedence over everything else
+    pub fn reincluded_files(mut self, add: Vec<String>) -> Self {
+        self.reincluded_files
+            .extend(add.into_iter().map(CopyDef::Simple));
+        self
+    }
+
+    /// names of glob presets, applied after the ones from "preset" in the config
+    pub fn presets(mut self, add: Vec<String>) -> Self {
+        self.presets.extend(add);
+        self
+    }
+
+    /// globs of files to leave out of the asar, like `**/*.map`.
+    /// these are applied after everything else, so they also exclude from node_modules
+    pub fn excluded_files(mut self, add: Vec<String>) -> Self {
+        self.excluded_files.extend(
+            add.into_iter()
+                .map(|g| CopyDef::Simple(format!("!{g}"))),
+        );
+        self
+    }
+
+    /// whether to pack the whole node_modules, in addition to the "files" globs
+    pub fn with_node_modules(mut self, enabled: bool) -> Self {
+        self.node_modules = enabled;
+        self
+    }
+
+    /// whether to pack the asar and copy the extra files. without them,
+    /// only the desktop entry, metainfo and icons are generated
+    pub fn with_resources(mut self, enabled: bool) -> Self {
+        self.resources = enabled;
+        self
+    }
+
+    /// whether to extract and optimize the icons
+    pub fn with_icons(mut self, enabled: bool) -> Self {
+        self.icons = enabled;
+        self
+    }
+
+    /// cache the optimized icons in this directory, to skip optimizing them in later builds
+    pub fn icon_cache_dir<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.icon_cache_dir = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// unpack directories containing native modules (`*.node` files) from the asar.
+    /// `radius` is how many levels above the directory of the native module are unpacked
+    /// (0 unpacks just the directory with the `.node` file)
+    pub fn unpack_native_modules(mut self, radius: usize) -> Self {
+        self.native_unpack_radius = Some(radius);
+        self
+    }
+
+    /// whether to pack files that symlinks point to. if disabled, symlinks are left out.
+    /// broken symlinks are always left out
+    pub fn follow_symlinks(mut self, enabled: bool) -> Self {
+        self.follow_symlinks = enabled;
+        self
+    }
+
+    /// limits on the files packed into the asar, which are reported if exceeded
+    pub fn asar_limits(mut self, limits: AsarLimits) -> Self {
+        self.asar_limits = limits;
+        self
+    }
+
+    /// go through the whole process, but don't write anything to disk
+    pub fn dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    /// fail instead of warning if the "main" entry point is not packed
+    pub fn strict_main(mut self, enabled: bool) -> Self {
+        self.strict_main = enabled;
+        self
+    }
+
+    /// fail instead of warning if the "from" of a file set does not exist,
+    /// or if a file set has neither "from" nor filters
+    pub fn strict_from(mut self, enabled: bool) -> Self {
+        self.strict_from = enabled;
+        self
+    }
+
+    /// fail instead of warning if "extraMetadata" is not an object
+    pub fn strict_extra_metadata(mut self, enabled: bool) -> Self {
+        self.strict_extra_metadata = enabled;
+        self
+    }
+
+    /// fail instead of warning if a protocol or file association is empty
+    pub fn strict_associations(mut self, enabled: bool) -> Self {
+        self.strict_associations = enabled;
+        self
+    }
+
+    /// how many threads to use at most. defaults to the number of cpus
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+
+    /// also write `app.asar.filelist`, with the sorted paths and sizes of the packed files,
+    /// for comparing the asar contents between releases
+    pub fn asar_filelist(mut self, enabled: bool) -> Self {
+        self.asar_filelist = enabled;
+        self
+    }
+
+    /// with this disabled, extra files that already exist in the output dir
+    /// with the same contents are not copied again, which speeds up re-runs
+    pub fn overwrite_dest(mut self, enabled: bool) -> Self {
+        self.overwrite_dest = enabled;
+        self
+    }
+
+    /// leave out paths marked with `export-ignore` in `.gitattributes` of the app
+    pub fn export_ignore(mut self, enabled: bool) -> Self {
+        self.export_ignore = enabled;
+        self
+    }
+
+    /// rules for renaming the packed files inside of the asar (and `app.asar.unpacked`).
+    /// only the first matching rule is applied to a path
+    pub fn path_rewrites(mut self, add: Vec<PathRewrite>) -> Self {
+        self.path_rewrites.extend(add);
+        self
+    }
+
+    /// how many bytes of the packed files to read in parallel, at most.
+    /// files bigger than this are read one by one. the read contents are still
+    /// kept in memory until the whole asar is written
+    pub fn read_batch_size(mut self, bytes: u64) -> Self {
+        self.read_batch_size = Some(bytes);
+        self
+    }
+
+    /// pack the source maps (`*.map` files), which are left out by default.
+    /// the "include-sourcemaps" preset does the same
+    pub fn include_sourcemaps(mut self, enabled: bool) -> Self {
+        self.include_sourcemaps = enabled;
+        self
+    }
+
+    /// whether to pack the hidden files and dirs (starting with a dot) matched by the globs.
+    /// when disabled, dotfiles are only packed if named explicitly in "files"
+    pub fn dotfiles(mut self, enabled: bool) -> Self {
+        self.dotfiles = enabled;
+        self
+    }
+
+    /// whether to leave out the usual junk files, like docs, tests and lockfiles.
+    /// defaults to "standardFilters" from the "tasje" key of package.json, or true
+    pub fn standard_filters(mut self, enabled: bool) -> Self {
+        self.standard_filters = enabled;
+        self
+    }
+
+    /// pack into a staging dir next to the output dir, and only replace the output dir
+    /// with it when done, so that the output dir is never seen incomplete
+    pub fn atomic_output(mut self, enabled: bool) -> Self {
+        self.atomic_output = enabled;
+        self
+    }
+
+    /// generator used for the desktop entry, for customizing its output
+    pub fn desktop_generator(mut self, generator: DesktopGenerator) -> Self {
+        self.desktop_generator = Some(generator);
+        self
+    }
+
+    /// generator used for the metainfo, for customizing its output
+    pub fn metainfo_generator(mut self, generator: MetainfoGenerator) -> Self {
+        self.metainfo_generator = Some(generator);
+        self
+    }
+
+    /// the builder can be reused, like for building the process for other targets.
+    /// the output dirs are resolved for the target on every build
+    pub fn build(&self) -> PackingProcess {
+        let builder = self.clone();
+        let environment = builder
             .target_environment
             .unwrap_or(HOST_ENVIRONMENT);
-        let base_output_dir = self.app.root.clone().join(
-            self.base_output_dir
-                .clone()
-                .unwrap_or_else(|| self.app.output_dir(environment.platform)),
-        );
+        // both are already resolved against the project root
+        let base_output_dir = builder
+            .base_output_dir
+            .clone()
+            .unwrap_or_else(|| builder.app.output_dir(environment.platform));
         let icons_output_dir = base_output_dir.join(
-            self.icons_output_dir
+            builder
+                .icons_output_dir
                 .unwrap_or_else(|| "icons".into()),
         );
         let resources_output_dir = base_output_dir.join(
-            self.resources_output_dir
+            builder
+                .resources_output_dir
                 .unwrap_or_else(|| "resources".into()),
         );
+        let extra_files_output_dir = match builder.extra_files_output_dir {
+            Some(dir) => base_output_dir.join(dir),
+            None => base_output_dir.clone(),
+        };
+        let extra_resources_output_dir = match builder.extra_resources_output_dir {
+            Some(dir) => base_output_dir.join(dir),
+            None => resources_output_dir.clone(),
+        };
+        let unpack_output_dir = match builder.unpack_output_dir {
+            Some(dir) => base_output_dir.join(dir),
+            None => resources_output_dir.join("app.asar.unpacked"),
+        };
         PackingProcess {
-            app: self.app,
+            app: builder.app,
             base_output_dir,
             icons_output_dir,
             resources_output_dir,
+            extra_files_output_dir,
+            extra_resources_output_dir,
+            unpack_output_dir,
             environment,
-            additional_files: self.additional_files,
-            additional_asar_unpack: self.additional_asar_unpack,
-            additional_extra_resources: self.additional_extra_resources,
+            additional_files: builder.additional_files,
+            additional_asar_unpack: builder.additional_asar_unpack,
+            additional_extra_resources: builder.additional_extra_resources,
+            reincluded_files: builder.reincluded_files,
+            excluded_files: builder.excluded_files,
+            presets: builder.presets,
+            desktop_generator: builder
+                .desktop_generator
+                .unwrap_or_else(DesktopGenerator::new),
+            metainfo_generator: builder
+                .metainfo_generator
+                .unwrap_or_else(MetainfoGenerator::new),
+            node_modules: builder.node_modules,
+            resources: builder.resources,
+            native_unpack_radius: builder.native_unpack_radius,
+            follow_symlinks: builder.follow_symlinks,
+            asar_limits: builder.asar_limits,
+            dry_run: builder.dry_run,
+            strict_main: builder.strict_main,
+            strict_from: builder.strict_from,
+            strict_extra_metadata: builder.strict_extra_metadata,
+            strict_associations: builder.strict_associations,
+            icons: builder.icons,
+            icon_cache_dir: builder.icon_cache_dir,
+            jobs: builder.jobs,
+            asar_filelist: builder.asar_filelist,
+            overwrite_dest: builder.overwrite_dest,
+            export_ignore: builder.export_ignore,
+            path_rewrites: builder.path_rewrites,
+            read_batch_size: builder.read_batch_size,
+            include_sourcemaps: builder.include_sourcemaps,
+            dotfiles: builder.dotfiles,
+            standard_filters: builder.standard_filters,
+            atomic_output: builder.atomic_output,
+            swapped_output_dir: None,
+            directories: DirectoryCreator::default(),
         }
     }
 }
 
+#[derive(Clone)]
 pub struct PackingProcess {
-    pub app: App,
+    pub app: Arc<App>,
     base_output_dir: PathBuf,
     icons_output_dir: PathBuf,
     resources_output_dir: PathBuf,
+    extra_files_output_dir: PathBuf,
+    extra_resources_output_dir: PathBuf,
+    unpack_output_dir: PathBuf,
     environment: Environment,
     additional_files: Vec<CopyDef>,
     additional_asar_unpack: Vec<String>,
     additional_extra_resources: Vec<CopyDef>,
+    reincluded_files: Vec<CopyDef>,
+    excluded_files: Vec<CopyDef>,
+    presets: Vec<String>,
+    desktop_generator: DesktopGenerator,
+    metainfo_generator: MetainfoGenerator,
+    node_modules: bool,
+    resources: bool,
+    native_unpack_radius: Option<usize>,
+    follow_symlinks: bool,
+    asar_limits: AsarLimits,
+    dry_run: bool,
+    strict_main: bool,
+    strict_from: bool,
+    strict_extra_metadata: bool,
+    strict_associations: bool,
+    icons: bool,
+    icon_cache_dir: Option<PathBuf>,
+    jobs: Option<usize>,
+    asar_filelist: bool,
+    overwrite_dest: bool,
+    export_ignore: bool,
+    path_rewrites: Vec<PathRewrite>,
+    read_batch_size: Option<u64>,
+    include_sourcemaps: bool,
+    dotfiles: bool,
+    standard_filters: bool,
+    atomic_output: bool,
+    /// the output dir replaced by this one when done, if this is a staging dir
+    swapped_output_dir: Option<PathBuf>,
+    directories: DirectoryCreator,
 }
 
 impl PackingProcess {
-    pub fn proceed(self) -> Result<()> {
-        fs::create_dir_all(&self.resources_output_dir)?;
-        fs::create_dir_all(&self.icons_output_dir)?;
+    pub fn base_output_dir(&self) -> &Path {
+        &self.base_output_dir
+    }
 
-        self.pack_asar()?;
-        self.pack_extra(
-            self.app
-                .config()
-                .extra_files(self.environment.platform),
-            &self.base_output_dir,
-        )?;
-        self.pack_extra(
-            self.app
-                .config()
-                .extra_resources(self.environment.platform),
-            &self.resources_output_dir,
-        )?;
+    pub fn proceed(&self) -> Result<PackSummary, TasjeError> {
+        // the output dir is replaced as a whole when packing atomically,
+        // so the unpacked files have to be in it too
+        let leaves_output = match self
+            .unpack_output_dir
+            .strip_prefix(&self.base_output_dir)
+        {
+            Ok(relative) => relative
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)),
+            Err(_) => true,
+        };
+        if leaves_output {
+            return Err(TasjeError::Config(anyhow!(
+                "the unpack dir {:?} is outside the output dir {:?}",
+                self.unpack_output_dir,
+                self.base_output_dir,
+            )));
+        }
+        if self.icons && !self.dry_run {
+            IconGenerator::check_sources(&self.app.icon_locations(self.environment.platform))?;
+        }
+        if self.atomic_output && !self.dry_run {
+            return self.proceed_staged();
+        }
+        self.thread_pool()?
+            .install(|| self.proceed_in_pool())
+    }
 
-        self.generate_desktop_file()?;
-        self.generate_icons()?;
+    /// packs into a staging dir next to the output dir, which then replaces the output dir
+    fn proceed_staged(&self) -> Result<PackSummary, TasjeError> {
+        let staging_dir = hidden_sibling(&self.base_output_dir, "staging");
+        let rebase = |dir: &Path, from: &Path, to: &Path| match dir.strip_prefix(from) {
+            Ok(relative) => to.join(relative),
+            Err(_) => dir.to_path_buf(),
+        };
+        let to_staging = |dir: &Path| rebase(dir, &self.base_output_dir, &staging_dir);
+        let staged = PackingProcess {
+            base_output_dir: staging_dir.clone(),
+            icons_output_dir: to_staging(&self.icons_output_dir),
+            resources_output_dir: to_staging(&self.resources_output_dir),
+            extra_files_output_dir: to_staging(&self.extra_files_output_dir),
+            extra_resources_output_dir: to_staging(&self.extra_resources_output_dir),
+            unpack_output_dir: to_staging(&self.unpack_output_dir),
+            atomic_output: false,
+            swapped_output_dir: Some(self.base_output_dir.clone()),
+            ..self.clone()
+        };
+        // left over from a run that was interrupted
+        if staging_dir.exists() {
+            fs::remove_dir_all(&staging_dir).map_err(|e| TasjeError::Output(e.into()))?;
+        }
+        let mut summary = match staged.proceed() {
+            Ok(summary) => summary,
+            Err(err) => {
+                let _ = fs::remove_dir_all(&staging_dir);
+                return Err(err);
+            }
+        };
+        replace_dir(&staging_dir, &self.base_output_dir).map_err(TasjeError::Output)?;
 
-        Ok(())
+        let lists = &mut summary.copy_lists;
+        for (_, dest) in lists
+            .unpacked
+            .iter_mut()
+            .chain(lists.extra.iter_mut())
+        {
+            *dest = rebase(dest, &staging_dir, &self.base_output_dir);
+        }
+        Ok(summary)
+    }
+
+    /// pool for all the parallel work, bounded by `jobs`
+    fn thread_pool(&self) -> Result<ThreadPool, TasjeError> {
+        Ok(ThreadPoolBuilder::new()
+            // 0 lets rayon pick the number of cpus
+            .num_threads(self.jobs.unwrap_or(0))
+            .build()?)
+    }
+
+    /// only packs the asar, writing it into `writer` instead of the output dir,
+    /// without the extra files, desktop entry, metainfo and icons
+    pub fn write_asar<W: Write + Send>(&self, mut writer: W) -> Result<PackSummary, TasjeError> {
+        self.thread_pool()?.install(|| {
+            let mut summary = self.new_summary();
+            self.pack_asar(&mut summary, Some(&mut writer))
+                .map_err(|e| TasjeError::classify(e, TasjeError::Asar))?;
+            Ok(summary)
+        })
+    }
+
+    /// paths and sizes of the files that would be packed into the asar,
+    /// like in `app.asar.filelist`. nothing is written
+    pub fn resolve_asar_filelist(&self) -> Result<BTreeMap<String, u64>, TasjeError> {
+        let process = PackingProcess {
+            dry_run: true,
+            ..self.clone()
+        };
+        process.thread_pool()?.install(|| {
+            let mut summary = process.new_summary();
+            process
+                .pack_asar(&mut summary, None)
+                .and_then(|_| {
+                    let package = self
+                        .app
+                        .patched_package(self.environment.platform)?;
+                    let mut filelist =
+                        BTreeMap::from([("package.json".to_string(), package.len() as u64)]);
+                    for (source, dest) in &summary.copy_lists.asar {
+                        filelist.insert(asar_path(dest), fs::metadata(source)?.len());
+                    }
+                    Ok(filelist)
+                })
+                .map_err(|e| TasjeError::classify(e, TasjeError::Asar))
+        })
+    }
+
+    fn new_summary(&self) -> PackSummary {
+        PackSummary {
+            node_version: self.app.node_version().map(str::to_string),
+            warnings: self
+                .app
+                .duplicate_config_keys()
+                .iter()
+                .map(|key| PackWarning::DuplicateConfigKey { key: key.clone() })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn proceed_in_pool(&self) -> Result<PackSummary, TasjeError> {
+        let mut summary = self.new_summary();
+
+        if !self.dry_run && self.resources {
+            self.directories
+                .create_dir_all(&self.resources_output_dir)
+                .map_err(TasjeError::Asar)?;
+        }
+        if !self.dry_run && self.icons {
+            self.directories
+                .create_dir_all(&self.icons_output_dir)
+                .map_err(TasjeError::Icon)?;
+        }
+
+        if self.resources {
+            self.pack_asar(&mut summary, None)
+                .map_err(|e| TasjeError::classify(e, TasjeError::Asar))?;
+
+            let start = Instant::now();
+            self.pack_extra(
+                &mut summary,
+                self.app
+                    .config()
+                    .extra_files(self.environment.platform),
+                &[],
+                &self.extra_files_output_dir,
+            )
+            .and_then(|_| {
+                self.pack_extra(
+                    &mut summary,
+                    self.app
+                        .config()
+                        .extra_resources(self.environment.platform),
+                    &self.additional_extra_resources,
+                    &self.extra_resources_output_dir,
+                )
+            })
+            .map_err(|e| TasjeError::classify(e, TasjeError::ExtraFiles))?;
+            summary.timings.extra_copy = start.elapsed();
+        }
+
+        let start = Instant::now();
+        self.generate_desktop_file(&mut summary)
+            .map_err(|e| TasjeError::classify(e, TasjeError::Desktop))?;
+        self.generate_metainfo()
+            .map_err(|e| TasjeError::classify(e, TasjeError::Metainfo))?;
+        summary.timings.desktop = start.elapsed();
+
+        let start = Instant::now();
+        self.generate_icons()
+            .map_err(|e| TasjeError::classify(e, TasjeError::Icon))?;
+        summary.timings.icons = start.elapsed();
+
+        Ok(summary)
     }
 
-    fn pack_asar(&self) -> Result<()> {
-        let mut asar = AsarWriter::new();
-        let asar_file = File::create(self.resources_output_dir.join("app.asar"))?;
-        let unpack_dir = self
-            .resources_output_dir
-            .join("app.asar.unpacked");
-        let mut files: Vec<&CopyDef> = vec![&NODE_MODULES_GLOB];
+    /// writes the asar into `sink` if given, otherwise into the resources dir
+    fn pack_asar(&self, summary: &mut PackSummary, sink: Option<&mut dyn Write>) -> Result<()> {
+        let mut asar = AsarWriter::new_with_algorithm(
+            self.app
+                .config()
+                .integrity_algorithm(self.environment.platform)
+                .map_err(TasjeError::Config)?,
+        );
+        let unpack_dir = &self.unpack_output_dir;
+        let mut files: Vec<&CopyDef> = Vec::new();
+        if self.node_modules {
+            files.push(&NODE_MODULES_GLOB);
+        }
         files.extend(self.app.config().files(self.environment.platform));
+        let preset_names = self
+            .app
+            .config()
+            .presets(self.environment.platform)
+            .iter()
+            .chain(self.presets.iter())
+            .collect::<Vec<_>>();
+        let presets = preset_files(&preset_names).map_err(TasjeError::Config)?;
+        files.extend(presets.iter());
+        let export_ignored = if self.export_ignore {
+            export_ignore_globs(&self.app.app_root(self.environment.platform))?
+                .into_iter()
+                .map(CopyDef::Simple)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        files.extend(export_ignored.iter());
+        if !self.dotfiles {
+            // the dotfiles named explicitly are still wanted, unless excluded after that,
+            // so the exclusions are applied again in the same order as well
+            let reapplied = files
+                .iter()
+                .copied()
+                .filter(|def| is_explicit_dotfile(def) || is_exclusion(def))
+                .collect::<Vec<_>>();
+            files.extend(DOTFILES_FILTERS.iter());
+            files.extend(reapplied);
+        }
+        let keep_lockfiles = self
+            .app
+            .config()
+            .keep_lockfiles(self.environment.platform);
+        let include_sourcemaps = self.include_sourcemaps
+            || preset_names
+                .iter()
+                .any(|name| *name == "include-sourcemaps");
+        let dropped_filters = [
+            (keep_lockfiles, LOCKFILES_FILTER),
+            (include_sourcemaps, SOURCEMAPS_FILTER),
+        ];
+        if self.standard_filters {
+            files.extend(FORCED_FILTERS.iter().filter(|f| {
+                !dropped_filters
+                    .iter()
+                    .any(|(dropped, filter)| *dropped && **f == CopyDef::Simple(filter.to_string()))
+            }));
+        }
+        let default_excludes = self
+            .app
+            .config()
+            .default_excludes(self.environment.platform)
+            .iter()
+            .map(|glob| {
+                // these are exclusions, with or without the "!"
+                let glob = glob.strip_prefix('!').unwrap_or(glob);
+                CopyDef::Simple(format!("!{glob}"))
+            })
+            .collect::<Vec<_>>();
+        files.extend(default_excludes.iter());
+        // the last matching glob wins, so these override the forced filters
         files.extend(self.additional_files.as_slice());
-        files.extend(FORCED_FILTERS.as_slice());
+        files.extend(self.reincluded_files.as_slice());
+        files.extend(self.excluded_files.as_slice());
         let unpack_list = Some(
             self.app
                 .config()
@@ -174,71 +1012,2660 @@ impl PackingProcess {
                 .collect::<Vec<_>>(),
         )
         .filter(|l| !l.is_empty());
+        let asar_enabled = self.app.config().asar(self.environment.platform);
+        if !asar_enabled {
+            if let Some(patterns) = &unpack_list {
+                summary
+                    .warnings
+                    .push(PackWarning::AsarUnpackIgnored {
+                        patterns: patterns.iter().map(|p| p.to_string()).collect(),
+                    });
+            }
+        }
+
+        if self
+            .app
+            .config()
+            .extra_metadata(self.environment.platform)
+            .is_some_and(|m| !m.is_object())
+        {
+            if self.strict_extra_metadata {
+                bail!(PackWarning::InvalidExtraMetadata);
+            }
+            summary
+                .warnings
+                .push(PackWarning::InvalidExtraMetadata);
+        }
 
         // adding package.json separately, to handle extraMetadata
-        asar.write_file(
-            "/package.json",
+        let package = self
+            .app
+            .patched_package(self.environment.platform)?;
+
+        let app_root = self.app.app_root(self.environment.platform);
+        let files = self.check_from(summary, &app_root, files)?;
+
+        // the previous output would be packed on re-runs, if it's inside the app.
+        // when staging, the previous output is still in the output dir being replaced
+        let outputs_in_app = [
+            Some(&self.base_output_dir),
+            self.swapped_output_dir.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(|dir| {
+            fs::canonicalize(dir)
+                .ok()
+                .zip(fs::canonicalize(&app_root).ok())
+                .and_then(|(output, root)| {
+                    output
+                        .strip_prefix(root)
+                        .ok()
+                        .map(Path::to_path_buf)
+                })
+        })
+        .collect::<Vec<_>>();
+        let mut output_matched = false;
+
+        let start = Instant::now();
+        let entries = Walker::new(
+            app_root.clone(),
+            self.environment,
+            files,
+            unpack_list.filter(|_| asar_enabled),
             self.app
-                .patched_package(self.environment.platform)?,
-            false,
-        )?;
+                .config()
+                .never_unpack(self.environment.platform)
+                .iter()
+                .collect(),
+            self.follow_symlinks,
+        )
+        .map_err(TasjeError::Walk)?
+        .filter(|(source, _, _)| {
+            let in_output = outputs_in_app.iter().any(|output| {
+                source
+                    .strip_prefix(&app_root)
+                    .is_ok_and(|s| s.starts_with(output))
+            });
+            output_matched |= in_output;
+            !in_output
+        })
+        .collect::<Vec<_>>();
+        let entries = self.rewrite_paths(entries)?;
+        if output_matched {
+            summary
+                .warnings
+                .push(PackWarning::OutputDirMatched {
+                    // the output dir being replaced, as the staging dir is only temporary
+                    dir: outputs_in_app.last().cloned().unwrap_or_default(),
+                });
+        }
+        let native_dirs = self
+            .native_unpack_radius
+            .map(|radius| native_module_dirs(entries.iter().map(|(_, dest, _)| dest), radius))
+            .unwrap_or_default();
+        // the native modules are found by their dests, after the walker applied neverUnpack,
+        // so it has to be applied to them again
+        let never_unpack = unpack_matcher(
+            self.app
+                .config()
+                .never_unpack(self.environment.platform)
+                .iter()
+                .cloned(),
+        )
+        .map_err(TasjeError::Config)?;
+        let in_native_dir = |dest: &Path| {
+            native_dirs.iter().any(|d| dest.starts_with(d))
+                && !never_unpack.evaluate_candidate(&globreeks::Candidate::new(dest))
+        };
+        summary.timings.walk = start.elapsed();
 
-        for (source, dest, unpack) in
-            Walker::new(self.app.root.clone(), self.environment, files, unpack_list)?
-        {
-            // always packing package.json above
-            if dest == Path::new("package.json") {
-                continue;
-            }
-            asar.write_file(ROOT.join(&dest), read(&source)?, true)?;
-            if unpack {
-                let unpack_dest = unpack_dir.join(dest);
-                fs::create_dir_all(unpack_dest.parent().unwrap())?;
-                fs::copy(&source, &unpack_dest)?;
+        let warnings = if asar_enabled {
+            self.check_asar_limits(&entries)?
+        } else {
+            Vec::new()
+        };
+        if self.asar_limits.strict {
+            if let Some(warning) = warnings.into_iter().next() {
+                bail!(warning);
             }
+        } else {
+            summary.warnings.extend(warnings);
         }
-        asar.finalize(asar_file)?;
 
-        Ok(())
-    }
+        if let Some(warning) = self.check_main(&entries) {
+            if self.strict_main {
+                bail!(warning);
+            }
+            summary.warnings.push(warning);
+        }
 
-    fn pack_extra<P>(&self, copydefs: &[CopyDef], target: P) -> Result<()>
-    where
-        P: AsRef<Path>,
-    {
-        let copydefs = copydefs
+        // always packing package.json above
+        let entries = entries
+            .into_iter()
+            .filter(|(_, dest, _)| dest != Path::new("package.json"))
+            .collect::<Vec<_>>();
+        summary.asar_files = entries.len() + 1;
+        let unpacked = entries
             .iter()
-            .chain(self.additional_extra_resources.iter().by_ref())
+            .filter(|(_, dest, unpack)| *unpack || in_native_dir(dest))
+            .filter(|_| asar_enabled)
             .collect::<Vec<_>>();
-        if copydefs.is_empty() {
-            // nothing to copy, don't bother looking
-            return Ok(());
+        summary.copy_lists.asar = entries
+            .iter()
+            .map(|(source, dest, _)| (source.clone(), dest.clone()))
+            .collect();
+        summary.copy_lists.unpacked = unpacked
+            .iter()
+            .map(|(source, dest, _)| (source.clone(), unpack_dir.join(dest)))
+            .collect();
+        if sink.is_some() {
+            if !asar_enabled {
+                bail!("there is no asar to write, as it is disabled in the config");
+            }
+            if !unpacked.is_empty() {
+                bail!(
+                    "{} files would be unpacked from the asar, which can't be written along with it",
+                    unpacked.len()
+                );
+            }
         }
-        let target = target.as_ref();
-        for (source, dest, _) in
-            Walker::new(self.app.root.clone(), self.environment, copydefs, None)?
-        {
-            let unpack_dest = target.join(dest);
-            fs::create_dir_all(unpack_dest.parent().unwrap())?;
-            fs::copy(&source, &unpack_dest)?;
+        if self.dry_run {
+            return Ok(());
         }
 
-        Ok(())
-    }
-
-    fn generate_desktop_file(&self) -> Result<()> {
-        if self.environment.platform == Platform::Linux {
-            DesktopGenerator::new().write_to_output_dir(
-                &self.app,
-                self.environment.platform,
-                Some(&self.base_output_dir),
+        let start = Instant::now();
+        if !asar_enabled {
+            self.copy_app_dir(package, &entries)?;
+            summary.timings.asar_write = start.elapsed();
+            return Ok(());
+        }
+        let mut filelist = vec![("package.json".to_string(), package.len())];
+        asar.write_file("/package.json", package, false)?;
+        let batches = match self.read_batch_size {
+            Some(budget) => read_batches(&entries, budget)?,
+            #[allow(clippy::single_range_in_vec_init)]
+            None => vec![0..entries.len()],
+        };
+        for batch in batches {
+            // reading in parallel, but writing in order, to keep the asar reproducible
+            let contents = entries[batch.clone()]
+                .par_iter()
+                .map(|(source, _, _)| read_source(source))
+                .collect::<Result<Vec<_>>>()?;
+            for ((_, dest, _), content) in entries[batch].iter().zip(contents) {
+                if self.asar_filelist {
+                    filelist.push((asar_path(dest), content.len()));
+                }
+                asar.write_file(ROOT.join(dest), content, true)?;
+            }
+        }
+        if let Some(sink) = sink {
+            asar.finalize(sink)?;
+            summary.timings.asar_write = start.elapsed();
+            return Ok(());
+        }
+        write_atomically(self.resources_output_dir.join("app.asar"), |asar_file| {
+            asar.finalize(asar_file)?;
+            Ok(())
+        })?;
+        if self.asar_filelist {
+            filelist.sort();
+            write_atomically(
+                self.resources_output_dir
+                    .join("app.asar.filelist"),
+                |file| {
+                    for (path, size) in &filelist {
+                        writeln!(file, "{path}\t{size}")?;
+                    }
+                    Ok(())
+                },
             )?;
         }
+        summary.timings.asar_write = start.elapsed();
+
+        let start = Instant::now();
+        unpacked
+            .par_iter()
+            .try_for_each(|(source, dest, _)| -> Result<()> {
+                // dests with a `to` from the config may mix `/` into windows paths,
+                // which windows accepts as a separator as well
+                let unpack_dest = unpack_dir.join(dest);
+                // native modules can be large, and rarely change between runs
+                if same_contents(source, &unpack_dest)? {
+                    return Ok(());
+                }
+                self.directories.create_parent(&unpack_dest)?;
+                fs::copy(source, &unpack_dest)?;
+                Ok(())
+            })?;
+        summary.timings.unpack_copy = start.elapsed();
 
         Ok(())
     }
 
-    fn generate_icons(&self) -> Result<()> {
-        IconGenerator::new().generate(self.app.icon_locations(), &self.icons_output_dir)
+    /// without an asar, the app is copied as it is into `resources/app`
+    fn copy_app_dir(&self, package: Vec<u8>, entries: &[(PathBuf, PathBuf, bool)]) -> Result<()> {
+        let app_dir = self.resources_output_dir.join("app");
+        self.directories.create_dir_all(&app_dir)?;
+        fs::write(app_dir.join("package.json"), package)?;
+        entries
+            .par_iter()
+            .try_for_each(|(source, dest, _)| -> Result<()> {
+                let app_dest = app_dir.join(dest);
+                self.directories.create_parent(&app_dest)?;
+                fs::copy(source, &app_dest)?;
+                Ok(())
+            })
+    }
+
+    fn rewrite_paths(
+        &self,
+        entries: Vec<(PathBuf, PathBuf, bool)>,
+    ) -> Result<Vec<(PathBuf, PathBuf, bool)>> {
+        if self.path_rewrites.is_empty() {
+            return Ok(entries);
+        }
+        let entries = entries
+            .into_iter()
+            .map(|(source, dest, unpack)| {
+                let dest = self
+                    .path_rewrites
+                    .iter()
+                    .find_map(|rewrite| rewrite.apply(&dest))
+                    .unwrap_or(dest);
+                (source, dest, unpack)
+            })
+            .collect::<Vec<_>>();
+        for (source, dest, _) in &entries {
+            if dest.as_os_str().is_empty()
+                || dest
+                    .components()
+                    .any(|c| !matches!(c, Component::Normal(_)))
+            {
+                bail!(TasjeError::Config(anyhow!(
+                    "{source:?} is packed as {dest:?} after rewriting paths, which is not a relative path inside the asar"
+                )));
+            }
+        }
+        // a rewrite can move a file onto another one, which would silently replace it
+        let mut sources = HashMap::new();
+        for (source, dest, _) in &entries {
+            if let Some(other) = sources.insert(dest, source) {
+                bail!("{other:?} and {source:?} are both packed as {dest:?} after rewriting paths");
+            }
+        }
+        Ok(entries)
+    }
+
+    fn check_asar_limits(&self, entries: &[(PathBuf, PathBuf, bool)]) -> Result<Vec<PackWarning>> {
+        let mut warnings = Vec::new();
+        if let Some(limit) = self.asar_limits.max_files {
+            // package.json is packed separately
+            let count = entries
+                .iter()
+                .filter(|(_, dest, _)| dest != Path::new("package.json"))
+                .count()
+                + 1;
+            if count > limit {
+                warnings.push(PackWarning::TooManyAsarFiles { count, limit });
+            }
+        }
+        if let Some(limit) = self.asar_limits.max_file_size {
+            for (source, dest, _) in entries {
+                let size = fs::metadata(source)?.len();
+                if size > limit {
+                    warnings.push(PackWarning::AsarFileTooLarge {
+                        path: dest.clone(),
+                        size,
+                        limit,
+                    });
+                }
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// electron resolves the entry point like node.js `require()` does
+    fn check_main(&self, entries: &[(PathBuf, PathBuf, bool)]) -> Option<PackWarning> {
+        let main = self.app.main(self.environment.platform);
+        let main_path = Path::new(main.strip_prefix("./").unwrap_or(main));
+        let candidates = [
+            main_path.to_path_buf(),
+            PathBuf::from(format!("{}.js", main_path.display())),
+            main_path.join("index.js"),
+        ];
+        if entries
+            .iter()
+            .any(|(_, dest, _)| candidates.contains(dest))
+        {
+            None
+        } else {
+            Some(PackWarning::MissingMain {
+                main: main.to_string(),
+            })
+        }
+    }
+
+    /// warns about sets whose "from" does not exist, which usually is a typo,
+    /// and leaves out sets with only "to", which would copy the whole app
+    fn check_from<'b>(
+        &self,
+        summary: &mut PackSummary,
+        root: &Path,
+        copydefs: Vec<&'b CopyDef>,
+    ) -> Result<Vec<&'b CopyDef>> {
+        let mut checked = Vec::with_capacity(copydefs.len());
+        for def in copydefs {
+            if let CopyDef::Set(set) = def {
+                let warning = match set.from().map(|f| root.join(expand_home(f))) {
+                    Some(from) if !from.exists() => Some(PackWarning::MissingFrom { from }),
+                    None if set.is_unbounded() => Some(PackWarning::UnboundedFileSet {
+                        to: set.to().unwrap_or_default().to_string(),
+                    }),
+                    _ => None,
+                };
+                if let Some(warning) = warning {
+                    if self.strict_from {
+                        bail!(warning);
+                    }
+                    let unbounded = matches!(warning, PackWarning::UnboundedFileSet { .. });
+                    summary.warnings.push(warning);
+                    if unbounded {
+                        continue;
+                    }
+                }
+            }
+            checked.push(def);
+        }
+        Ok(checked)
+    }
+
+    fn pack_extra<P>(
+        &self,
+        summary: &mut PackSummary,
+        copydefs: &[CopyDef],
+        additional: &[CopyDef],
+        target: P,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let copydefs = copydefs
+            .iter()
+            .chain(additional.iter())
+            .collect::<Vec<_>>();
+        if copydefs.is_empty() {
+            // nothing to copy, don't bother looking
+            return Ok(());
+        }
+        let target = target.as_ref();
+        let copydefs = self.check_from(summary, &self.app.root, copydefs)?;
+        let walker = Walker::new(
+            self.app.root.clone(),
+            self.environment,
+            copydefs,
+            None,
+            Vec::new(),
+            self.follow_symlinks,
+        )
+        .map_err(TasjeError::Walk)?;
+        for (source, dest, _) in walker {
+            let unpack_dest = self.extra_destination(target, &dest)?;
+            summary.extra_files += 1;
+            summary
+                .copy_lists
+                .extra
+                .push((source.clone(), unpack_dest.clone()));
+            if self.dry_run {
+                continue;
+            }
+            if !self.overwrite_dest && same_contents(&source, &unpack_dest)? {
+                summary.skipped_extra_files += 1;
+                continue;
+            }
+            self.directories.create_parent(&unpack_dest)?;
+            fs::copy(&source, &unpack_dest)?;
+        }
+
+        Ok(())
+    }
+
+    /// `to` starting with `${output}/` places the files relative to the base output dir,
+    /// instead of the target of the given copy kind
+    fn extra_destination(&self, target: &Path, dest: &Path) -> Result<PathBuf> {
+        let (base, relative) = match dest.strip_prefix(OUTPUT_PLACEHOLDER) {
+            Ok(relative) => (self.base_output_dir.as_path(), relative),
+            Err(_) => (target, dest),
+        };
+        if relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            bail!("extra file destination escapes the output directory: {dest:?}");
+        }
+        Ok(base.join(relative))
+    }
+
+    fn generate_desktop_file(&self, summary: &mut PackSummary) -> Result<()> {
+        if self.environment.platform == Platform::Linux {
+            summary.warnings.extend(
+                nonstandard_keys(&self.app, self.environment.platform)
+                    .into_iter()
+                    .map(|key| PackWarning::NonstandardDesktopKey { key }),
+            );
+            let mut empty = empty_associations(&self.app, self.environment.platform)
+                .into_iter()
+                .map(|key| PackWarning::EmptyAssociation { key });
+            if self.strict_associations {
+                if let Some(warning) = empty.next() {
+                    bail!(warning);
+                }
+            }
+            summary.warnings.extend(empty);
+            summary.warnings.extend(
+                inferred_mime_types(&self.app, self.environment.platform)
+                    .into_iter()
+                    .map(|(ext, mime_type)| PackWarning::InferredMimeType { ext, mime_type }),
+            );
+            if self.dry_run {
+                self.desktop_generator
+                    .clone()
+                    .generate_all(&self.app, self.environment.platform)?;
+                return Ok(());
+            }
+            self.desktop_generator
+                .clone()
+                .write_to_output_dir(
+                    &self.app,
+                    self.environment.platform,
+                    Some(&self.base_output_dir),
+                )?;
+        }
+
+        Ok(())
+    }
+
+    fn generate_metainfo(&self) -> Result<()> {
+        if self.environment.platform == Platform::Linux {
+            if self.dry_run {
+                self.metainfo_generator
+                    .clone()
+                    .generate(&self.app, self.environment.platform)?;
+                return Ok(());
+            }
+            self.metainfo_generator
+                .clone()
+                .write_to_output_dir(
+                    &self.app,
+                    self.environment.platform,
+                    Some(&self.base_output_dir),
+                )?;
+        }
+
+        Ok(())
+    }
+
+    fn generate_icons(&self) -> Result<()> {
+        if self.dry_run || !self.icons {
+            return Ok(());
+        }
+        let mut generator = IconGenerator::new();
+        let sizes = self
+            .app
+            .config()
+            .icon_sizes(self.environment.platform)
+            .map_err(TasjeError::Config)?;
+        if !sizes.is_empty() {
+            generator = generator.svg_sizes(sizes.to_vec());
+        }
+        if let Some(cache_dir) = &self.icon_cache_dir {
+            generator = generator.cache_dir(cache_dir);
+        }
+        Ok(generator.generate(
+            self.app.icon_locations(self.environment.platform),
+            &self.icons_output_dir,
+        )?)
+    }
+}
+
+/// whether the glob includes hidden files by name, like ".env" or "config/.npmrc"
+fn is_explicit_dotfile(def: &CopyDef) -> bool {
+    match def {
+        CopyDef::Simple(glob) if !glob.starts_with('!') => glob
+            .split('/')
+            .any(|segment| segment.starts_with('.') && segment != "." && segment != ".."),
+        _ => false,
+    }
+}
+
+fn is_exclusion(def: &CopyDef) -> bool {
+    matches!(def, CopyDef::Simple(glob) if glob.starts_with('!'))
+}
+
+/// path inside of the asar, with forward slashes on every platform
+fn asar_path(dest: &Path) -> String {
+    dest.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn read_source(path: &Path) -> Result<Vec<u8>> {
+    read(path).map_err(|source| read_error(path, source))
+}
+
+/// the walker already found the file, so failing to read it is worth explaining
+fn read_error(path: &Path, source: io::Error) -> anyhow::Error {
+    match source.kind() {
+        ErrorKind::PermissionDenied => TasjeError::PermissionDenied {
+            path: path.to_path_buf(),
+            source,
+        }
+        .into(),
+        ErrorKind::NotFound => TasjeError::FileNotFound {
+            path: path.to_path_buf(),
+            source,
+        }
+        .into(),
+        _ => anyhow::Error::new(source).context(format!("reading {path:?}")),
+    }
+}
+
+/// consecutive ranges of entries, which are read in parallel together,
+/// with their sizes adding up to at most `budget` bytes.
+/// a file bigger than the budget is read on its own
+fn read_batches(entries: &[(PathBuf, PathBuf, bool)], budget: u64) -> Result<Vec<Range<usize>>> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut in_flight = 0;
+    for (i, (source, _, _)) in entries.iter().enumerate() {
+        let size = fs::metadata(source)?.len();
+        if i > start && in_flight + size > budget {
+            batches.push(start..i);
+            start = i;
+            in_flight = 0;
+        }
+        in_flight += size;
+    }
+    if start < entries.len() {
+        batches.push(start..entries.len());
+    }
+    Ok(batches)
+}
+
+/// directories around native modules, which have to be unpacked for them to load
+fn native_module_dirs<'a, I>(dests: I, radius: usize) -> Vec<PathBuf>
+where
+    I: Iterator<Item = &'a PathBuf>,
+{
+    let mut dirs = Vec::new();
+    for dest in dests.filter(|d| d.extension() == Some(OsStr::new("node"))) {
+        let mut dir = dest
+            .parent()
+            .filter(|p| *p != Path::new(""))
+            .unwrap_or(dest);
+        for _ in 0..radius {
+            match dir.parent() {
+                // never unpack the whole asar root
+                Some(parent) if parent != Path::new("") => dir = parent,
+                _ => break,
+            }
+        }
+        if !dirs.iter().any(|d: &PathBuf| dir.starts_with(d)) {
+            dirs.retain(|d| !d.starts_with(dir));
+            dirs.push(dir.to_path_buf());
+        }
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        native_module_dirs, parse_asar_filelist, read_batches, read_error, AsarLimits,
+        FileListDiff, PackWarning, PackingProcessBuilder, PathRewrite,
+    };
+    use crate::app::App;
+    use crate::config::CopyDef;
+    use crate::environment::{Environment, Platform, HOST_ARCHITECTURE};
+    use crate::error::TasjeError;
+    use anyhow::Result;
+    use asar::{AsarReader, HashAlgorithm, Header};
+    use rayon::prelude::*;
+    use regex::Regex;
+    use serde_json::json;
+    use std::collections::{BTreeMap, HashSet};
+    use std::env::current_dir;
+    use std::fs;
+    use std::io::{self, ErrorKind};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+
+    static LINUX: Platform = Platform::Linux;
+
+    fn asar_files(output_dir: &Path) -> Result<Vec<PathBuf>> {
+        let asar_file = fs::read(output_dir.join("resources/app.asar"))?;
+        let asar = AsarReader::new(&asar_file, None)?;
+        Ok(asar.files().keys().cloned().collect())
+    }
+
+    #[test]
+    fn test_asar_filelist() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_asar_filelist");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .asar_filelist(true)
+            .build()
+            .proceed()?;
+
+        let asar_file = fs::read(output_dir.join("resources/app.asar"))?;
+        let asar = AsarReader::new(&asar_file, None)?;
+        let filelist = fs::read_to_string(output_dir.join("resources/app.asar.filelist"))?;
+        let listed = filelist
+            .lines()
+            .map(|l| l.split_once('\t').unwrap())
+            .collect::<Vec<_>>();
+
+        let mut sorted = listed.clone();
+        sorted.sort();
+        assert_eq!(listed, sorted);
+        assert_eq!(listed.len(), asar.files().len());
+        for (path, size) in listed {
+            let file = &asar.files()[&PathBuf::from(path)];
+            assert_eq!(size.parse::<usize>()?, file.data().len());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_node_modules() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_node_modules");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .build()
+            .proceed()?;
+        let files = asar_files(&output_dir)?;
+        assert!(files.contains(&PathBuf::from("node_modules/tasje_dep/index.js")));
+        assert!(files.contains(&PathBuf::from("cuild/bundle.aoeuid.js")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_node_modules() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_no_node_modules");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .build()
+            .proceed()?;
+        let files = asar_files(&output_dir)?;
+        assert!(!files
+            .iter()
+            .any(|f| f.starts_with("node_modules")));
+        assert!(files.contains(&PathBuf::from("cuild/bundle.aoeuid.js")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unpack_native_modules() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_native_modules");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .unpack_native_modules(0)
+            .build()
+            .proceed()?;
+        let unpacked = output_dir.join("resources/app.asar.unpacked/node_modules/ffi");
+        assert!(unpacked
+            .join("build/Release/ffi_bindings.node")
+            .is_file());
+        assert!(unpacked
+            .join("build/Release/libffi.so.8")
+            .is_file());
+        assert!(!unpacked.join("index.js").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unpack_native_modules_never_unpack() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_native_modules_never_unpack");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir)?;
+        }
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "node_modules/ffi/index.js",
+            }),
+            json!({
+                "neverUnpack": "**/libffi.so.*",
+            }),
+            "test_assets".into(),
+        )?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_icons(false)
+            .unpack_native_modules(0)
+            .build()
+            .proceed()?;
+        let unpacked = output_dir.join("resources/app.asar.unpacked/node_modules/ffi");
+        assert!(unpacked
+            .join("build/Release/ffi_bindings.node")
+            .is_file());
+        assert!(!unpacked
+            .join("build/Release/libffi.so.8")
+            .exists());
+
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_unpack_windows_paths() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_unpack_windows_paths");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "native/ffi/index.js",
+            }),
+            json!({
+                "files": [{
+                    "from": "node_modules/ffi",
+                    "to": "native/ffi",
+                }],
+                "asarUnpack": ["**/*.node"],
+            }),
+            "test_assets".into(),
+        )?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .build()
+            .proceed()?;
+        let unpacked = output_dir.join(r"resources\app.asar.unpacked\native\ffi\build\Release");
+        assert!(unpacked.is_dir());
+        assert!(unpacked.join("ffi_bindings.node").is_file());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_lists() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_copy_lists");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        let summary = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .unpack_native_modules(0)
+            .additional_extra_resources(vec![CopyDef::Simple("docs/*.md".to_string())])
+            .dry_run(true)
+            .build()
+            .proceed()?;
+        let lists = summary.copy_lists;
+        assert!(lists.asar.contains(&(
+            PathBuf::from("test_assets/build/bundle.aoeuid.js"),
+            PathBuf::from("cuild/bundle.aoeuid.js"),
+        )));
+        assert_eq!(lists.asar.len() + 1, summary.asar_files);
+        assert!(lists.unpacked.contains(&(
+            PathBuf::from("test_assets/node_modules/ffi/build/Release/ffi_bindings.node"),
+            output_dir.join(
+                "resources/app.asar.unpacked/node_modules/ffi/build/Release/ffi_bindings.node"
+            ),
+        )));
+        assert_eq!(
+            lists.extra,
+            [(
+                PathBuf::from("test_assets/docs/guide.md"),
+                output_dir.join("resources/docs/guide.md"),
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_overwrite_dest() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_overwrite_dest");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+                "extraResources": ["docs/*.md"],
+            }),
+            "test_assets".into(),
+        )?;
+        let dest = output_dir.join("resources/docs/guide.md");
+        PackingProcessBuilder::new(app.clone())
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .build()
+            .proceed()?;
+        let old_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&dest)?
+            .set_modified(old_time)?;
+
+        let summary = PackingProcessBuilder::new(app.clone())
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .overwrite_dest(false)
+            .build()
+            .proceed()?;
+        assert_eq!(summary.skipped_extra_files, 1);
+        assert_eq!(fs::metadata(&dest)?.modified()?, old_time);
+
+        let summary = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .build()
+            .proceed()?;
+        assert_eq!(summary.skipped_extra_files, 0);
+        assert_ne!(fs::metadata(&dest)?.modified()?, old_time);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unpack_output_dir() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_unpack_output_dir");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir)?;
+        }
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+                "asarUnpack": "node_modules/ffi",
+            }),
+            "test_assets".into(),
+        )?;
+        let summary = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_icons(false)
+            .unpack_output_dir("unpacked")
+            .build()
+            .proceed()?;
+        let unpacked = output_dir.join("unpacked/node_modules/ffi");
+        assert!(unpacked.join("index.js").is_file());
+        assert!(unpacked
+            .join("build/Release/ffi_bindings.node")
+            .is_file());
+        assert!(!output_dir
+            .join("resources/app.asar.unpacked")
+            .exists());
+        assert!(summary
+            .copy_lists
+            .unpacked
+            .iter()
+            .all(|(_, dest)| dest.starts_with(output_dir.join("unpacked"))));
+
+        // the asar writer can not mark files as unpacked, so they are packed in full as well
+        let asar_file = fs::read(output_dir.join("resources/app.asar"))?;
+        let (header, _) = Header::read(&mut &asar_file[..])?;
+        let unpacked = ["node_modules", "ffi", "index.js"]
+            .into_iter()
+            .try_fold(&header, |header, name| match header {
+                Header::Directory { files } => files.get(name),
+                _ => None,
+            });
+        assert!(matches!(unpacked, Some(Header::File(file)) if !file.unpacked()));
+        let asar = AsarReader::new(&asar_file, None)?;
+        assert_eq!(
+            asar.read(Path::new("node_modules/ffi/index.js"))
+                .unwrap()
+                .data(),
+            fs::read(output_dir.join("unpacked/node_modules/ffi/index.js"))?
+        );
+
+        for dir in ["../unpacked", "/tmp/unpacked"] {
+            let err =
+                PackingProcessBuilder::new(App::new_from_package_file("test_assets/package.json")?)
+                    .base_output_dir(&output_dir)
+                    .with_icons(false)
+                    .unpack_output_dir(dir)
+                    .build()
+                    .proceed()
+                    .unwrap_err();
+            assert!(matches!(err, TasjeError::Config(_)), "{dir}: {err}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_asar_unpack_dir() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_asar_unpack_dir");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+                "asarUnpack": "node_modules/ffi",
+            }),
+            "test_assets".into(),
+        )?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .build()
+            .proceed()?;
+        let unpacked = output_dir.join("resources/app.asar.unpacked/node_modules");
+        assert!(unpacked.join("ffi/index.js").is_file());
+        assert!(unpacked
+            .join("ffi/build/Release/ffi_bindings.node")
+            .is_file());
+        assert!(unpacked
+            .join("ffi/prebuilds/foo/ffi.node")
+            .is_file());
+        assert!(!unpacked.join("tasje_dep").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_asar_unpack_anchored() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_asar_unpack_anchored");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir)?;
+        }
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+                "asarUnpack": ["/node_modules/ffi", "/ffi_bindings.node"],
+            }),
+            "test_assets".into(),
+        )?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_icons(false)
+            .build()
+            .proceed()?;
+        let unpacked = output_dir.join("resources/app.asar.unpacked/node_modules");
+        assert!(unpacked.join("ffi/index.js").is_file());
+        assert!(!unpacked.join("tasje_dep").exists());
+
+        // anchored to the root, so the nested one is not matched
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+                "asarUnpack": "/ffi_bindings.node",
+            }),
+            "test_assets".into(),
+        )?;
+        fs::remove_dir_all(&output_dir)?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_icons(false)
+            .build()
+            .proceed()?;
+        assert!(!output_dir
+            .join("resources/app.asar.unpacked")
+            .exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_dir_inside_app() -> Result<()> {
+        let root = current_dir()?.join(".test-workspace/pack_output_dir_inside_app");
+        if root.exists() {
+            fs::remove_dir_all(&root)?;
+        }
+        fs::create_dir_all(&root)?;
+        fs::write(root.join("index.js"), "")?;
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["**/*"],
+                "directories": {
+                    "output": "dist",
+                },
+            }),
+            root.clone(),
+        )?;
+        for run in 0..2 {
+            let summary = PackingProcessBuilder::new(app.clone())
+                .with_icons(false)
+                .build()
+                .proceed()?;
+            let files = asar_files(&root.join("dist"))?;
+            assert_eq!(files.len(), 2);
+            assert!(files.contains(&PathBuf::from("index.js")));
+            // nothing to match yet on the first run
+            assert_eq!(
+                summary.warnings,
+                if run == 0 {
+                    vec![]
+                } else {
+                    vec![PackWarning::OutputDirMatched {
+                        dir: PathBuf::from("dist"),
+                    }]
+                }
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unpacked_not_recopied() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_unpacked_not_recopied");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        let dest = output_dir
+            .join("resources/app.asar.unpacked/node_modules/ffi/build/Release/ffi_bindings.node");
+        PackingProcessBuilder::new(app.clone())
+            .base_output_dir(&output_dir)
+            .unpack_native_modules(0)
+            .build()
+            .proceed()?;
+        let old_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&dest)?
+            .set_modified(old_time)?;
+
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .unpack_native_modules(0)
+            .build()
+            .proceed()?;
+        assert_eq!(fs::metadata(&dest)?.modified()?, old_time);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tarball() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_tarball");
+        let app = App::new_from_tarball("test_assets/tarball/tasje_tarball-1.0.0.tgz")?;
+        let extracted = app.root.clone();
+        assert!(extracted.join("build/index.js").is_file());
+        let summary = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .build()
+            .proceed()?;
+        assert!(summary.warnings.is_empty());
+        let files = asar_files(&output_dir)?;
+        assert!(files.contains(&PathBuf::from("build/index.js")));
+        // the app was dropped along with the packing process
+        assert!(!extracted.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extra_output_dirs() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_extra_output_dirs");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir)?;
+        }
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+                "extraFiles": ["build/*.js"],
+                "extraResources": ["docs/*.md"],
+            }),
+            "test_assets".into(),
+        )?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .extra_files_output_dir("bin")
+            .extra_resources_output_dir("lib/resources")
+            .build()
+            .proceed()?;
+        assert!(output_dir
+            .join("bin/build/bundle.aoeuid.js")
+            .is_file());
+        assert!(output_dir
+            .join("lib/resources/docs/guide.md")
+            .is_file());
+        assert!(!output_dir.join("resources/docs").exists());
+        assert!(!output_dir.join("bin/docs").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_rewrites() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_path_rewrites");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        PackingProcessBuilder::new(app.clone())
+            .base_output_dir(&output_dir)
+            .path_rewrites(vec![PathRewrite {
+                pattern: Regex::new(r"^node_modules/@[^/]+/([^/]+)")?,
+                replacement: "node_modules/$1".to_string(),
+            }])
+            .with_icons(false)
+            .build()
+            .proceed()?;
+        let files = asar_files(&output_dir)?;
+        assert!(files.contains(&PathBuf::from("node_modules/scoped/index.js")));
+        assert!(!files.contains(&PathBuf::from("node_modules/@tasje/scoped/index.js")));
+
+        let err = PackingProcessBuilder::new(app.clone())
+            .base_output_dir(&output_dir)
+            .path_rewrites(vec![PathRewrite {
+                pattern: Regex::new(r"^[bc]uild/")?,
+                replacement: "build/".to_string(),
+            }])
+            .with_icons(false)
+            .dry_run(true)
+            .build()
+            .proceed()
+            .unwrap_err();
+        assert!(err.to_string().contains("after rewriting paths"));
+
+        let pattern = Regex::new(r"^build/.*")?;
+        for replacement in ["../", "/", "./", ""] {
+            let err = PackingProcessBuilder::new(app.clone())
+                .base_output_dir(&output_dir)
+                .path_rewrites(vec![PathRewrite {
+                    pattern: pattern.clone(),
+                    replacement: replacement.to_string(),
+                }])
+                .with_icons(false)
+                .dry_run(true)
+                .build()
+                .proceed()
+                .unwrap_err();
+            assert!(
+                matches!(&err, TasjeError::Config(e) if e.to_string().contains("not a relative path")),
+                "{replacement:?}: {err}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_batches() -> Result<()> {
+        let root = current_dir()?.join(".test-workspace/pack_read_batches");
+        fs::create_dir_all(&root)?;
+        let entries = [("a", 4), ("b", 4), ("big", 20), ("c", 2), ("d", 8)]
+            .into_iter()
+            .map(|(name, size)| {
+                let path = root.join(name);
+                fs::write(&path, vec![0u8; size])?;
+                Ok((path, PathBuf::from(name), false))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let batches = read_batches(&entries, 10)?;
+        assert_eq!(batches, [0..2, 2..3, 3..5]);
+        for batch in batches.into_iter().filter(|b| b.len() > 1) {
+            let size: u64 = entries[batch]
+                .iter()
+                .map(|(path, _, _)| fs::metadata(path).unwrap().len())
+                .sum();
+            assert!(size <= 10);
+        }
+        // every file on its own
+        assert_eq!(read_batches(&entries, 1)?.len(), entries.len());
+
+        let output_dir = current_dir()?.join(".test-workspace/pack_read_batch_size");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        PackingProcessBuilder::new(app.clone())
+            .base_output_dir(output_dir.join("unbounded"))
+            .with_icons(false)
+            .build()
+            .proceed()?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(output_dir.join("bounded"))
+            .read_batch_size(1)
+            .with_icons(false)
+            .build()
+            .proceed()?;
+        // the order of the header is random, so comparing the files instead
+        let bounded = fs::read(output_dir.join("bounded/resources/app.asar"))?;
+        let unbounded = fs::read(output_dir.join("unbounded/resources/app.asar"))?;
+        let bounded = AsarReader::new(&bounded, None)?;
+        let unbounded = AsarReader::new(&unbounded, None)?;
+        assert_eq!(bounded.files().len(), unbounded.files().len());
+        for (path, file) in unbounded.files() {
+            assert_eq!(bounded.files()[path].data(), file.data());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_ignore() -> Result<()> {
+        let root = current_dir()?.join(".test-workspace/pack_export_ignore");
+        if root.exists() {
+            fs::remove_dir_all(&root)?;
+        }
+        fs::create_dir_all(root.join("tests"))?;
+        fs::write(root.join("index.js"), "")?;
+        fs::write(root.join("tests/index.js"), "")?;
+        fs::write(root.join(".gitattributes"), "/tests export-ignore\n")?;
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["**/*.js"],
+            }),
+            root.clone(),
+        )?;
+        for (enabled, expected) in [
+            (false, vec!["index.js", "package.json", "tests/index.js"]),
+            (true, vec!["index.js", "package.json"]),
+        ] {
+            let output_dir = root.join("out");
+            PackingProcessBuilder::new(app.clone())
+                .base_output_dir(&output_dir)
+                .export_ignore(enabled)
+                .with_icons(false)
+                .build()
+                .proceed()?;
+            let mut files = asar_files(&output_dir)?
+                .into_iter()
+                .collect::<Vec<_>>();
+            files.sort();
+            assert_eq!(
+                files,
+                expected
+                    .into_iter()
+                    .map(PathBuf::from)
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_native_module_dirs() {
+        let dests = [
+            PathBuf::from("node_modules/ffi/build/Release/ffi_bindings.node"),
+            PathBuf::from("node_modules/ffi/build/Release/ffi_other.node"),
+            PathBuf::from("node_modules/ffi/index.js"),
+            PathBuf::from("native.node"),
+        ];
+        assert_eq!(
+            native_module_dirs(dests.iter(), 0),
+            [
+                PathBuf::from("node_modules/ffi/build/Release"),
+                PathBuf::from("native.node"),
+            ]
+        );
+        assert_eq!(
+            native_module_dirs(dests.iter(), 2),
+            [
+                PathBuf::from("node_modules/ffi"),
+                PathBuf::from("native.node"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reincluded_files() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_reincluded_files");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .reincluded_files(vec!["node_modules/tasje_dep/data.md".to_string()])
+            .build()
+            .proceed()?;
+        let files = asar_files(&output_dir)?;
+        assert!(files.contains(&PathBuf::from("node_modules/tasje_dep/data.md")));
+        assert!(!files.contains(&PathBuf::from("node_modules/tasje_dep/README.md")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_excluded_files() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_excluded_files");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .excluded_files(vec!["**/*.map".to_string()])
+            .build()
+            .proceed()?;
+        let files = asar_files(&output_dir)?;
+        assert!(files.contains(&PathBuf::from("node_modules/tasje_dep/index.js")));
+        assert!(!files.contains(&PathBuf::from("node_modules/tasje_dep/index.js.map")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_presets() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_presets");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "build/bundle.aoeuid.js",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+                "preset": "minimal-node",
+            }),
+            "test_assets".into(),
+        )?;
+        PackingProcessBuilder::new(app.clone())
+            .base_output_dir(&output_dir)
+            .build()
+            .proceed()?;
+        let files = asar_files(&output_dir)?;
+        assert!(files.contains(&PathBuf::from("node_modules/tasje_dep/index.js")));
+        assert!(!files.contains(&PathBuf::from("node_modules/tasje_dep/index.js.map")));
+
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .presets(vec!["include-sourcemaps".to_string()])
+            .build()
+            .proceed()?;
+        let files = asar_files(&output_dir)?;
+        assert!(files.contains(&PathBuf::from("node_modules/tasje_dep/index.js.map")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sourcemaps() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_sourcemaps");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["**/*"],
+            }),
+            "test_assets".into(),
+        )?;
+        for (include, packed) in [(false, false), (true, true)] {
+            PackingProcessBuilder::new(app.clone())
+                .base_output_dir(&output_dir)
+                .with_icons(false)
+                .include_sourcemaps(include)
+                .build()
+                .proceed()?;
+            let files = asar_files(&output_dir)?;
+            assert!(files.contains(&PathBuf::from("node_modules/tasje_dep/index.js")));
+            assert_eq!(
+                files.contains(&PathBuf::from("node_modules/tasje_dep/index.js.map")),
+                packed
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_additional_files_precedence() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_additional_files_precedence");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .additional_files(vec![CopyDef::Simple("docs/*.md".to_string())])
+            .build()
+            .proceed()?;
+        let files = asar_files(&output_dir)?;
+        assert!(files.contains(&PathBuf::from("docs/guide.md")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filelist_diff() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_filelist_diff");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        let process = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_icons(false)
+            .asar_filelist(true)
+            .build();
+        process.proceed()?;
+        let filelist = fs::read_to_string(output_dir.join("resources/app.asar.filelist"))?;
+        let old = parse_asar_filelist(&filelist)?;
+        let new = process.resolve_asar_filelist()?;
+        assert_eq!(old, new);
+        assert!(FileListDiff::new(&old, &new).is_empty());
+
+        // a previous build without one of the files, and with another one bigger
+        let old = filelist
+            .lines()
+            .filter(|line| !line.starts_with("node_modules/tasje_dep/index.js\t"))
+            .map(|line| match line.split_once('\t') {
+                Some(("package.json", size)) => {
+                    format!("package.json\t{}", size.parse::<u64>().unwrap() + 1)
+                }
+                _ => line.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let diff = FileListDiff::new(&parse_asar_filelist(&old)?, &new);
+        let index_size = new["node_modules/tasje_dep/index.js"];
+        let package_size = new["package.json"];
+        assert_eq!(
+            diff,
+            FileListDiff {
+                added: vec![("node_modules/tasje_dep/index.js".to_string(), index_size)],
+                removed: vec![],
+                resized: vec![("package.json".to_string(), package_size + 1, package_size)],
+            }
+        );
+
+        assert_eq!(
+            parse_asar_filelist("a\tb.js\t3")?,
+            BTreeMap::from([("a\tb.js".to_string(), 3)])
+        );
+        assert!(parse_asar_filelist("package.json").is_err());
+        assert!(parse_asar_filelist("package.json\tbig").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_asar_disabled() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_asar_disabled");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir)?;
+        }
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "build/bundle.aoeuid.js",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+                "asar": false,
+                "asarUnpack": ["**/*.node"],
+            }),
+            "test_assets".into(),
+        )?;
+        let summary = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .build()
+            .proceed()?;
+        assert_eq!(
+            summary.warnings,
+            [PackWarning::AsarUnpackIgnored {
+                patterns: vec!["**/*.node".to_string()]
+            }]
+        );
+        assert!(!output_dir.join("resources/app.asar").exists());
+        assert!(output_dir
+            .join("resources/app/build/bundle.aoeuid.js")
+            .is_file());
+        assert!(output_dir
+            .join("resources/app/package.json")
+            .is_file());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_extra_metadata() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_invalid_extra_metadata");
+        let package = json!({
+            "name": "tasje",
+            "version": "1.0.0",
+            "main": "build/bundle.aoeuid.js",
+        });
+        let app = App::from_values(
+            package.clone(),
+            json!({
+                "files": ["/build/**/*"],
+                "extraMetadata": ["main", "index.js"],
+            }),
+            "test_assets".into(),
+        )?;
+        let builder = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .with_icons(false);
+        let summary = builder.clone().build().proceed()?;
+        assert_eq!(summary.warnings, [PackWarning::InvalidExtraMetadata]);
+
+        let asar_file = fs::read(output_dir.join("resources/app.asar"))?;
+        let asar = AsarReader::new(&asar_file, None)?;
+        let packed = asar.read(Path::new("package.json")).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(packed.data())?,
+            package
+        );
+
+        assert!(matches!(
+            builder
+                .strict_extra_metadata(true)
+                .build()
+                .proceed(),
+            Err(TasjeError::Strict(PackWarning::InvalidExtraMetadata))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_never_unpack() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_never_unpack");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir)?;
+        }
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "node_modules/ffi/index.js",
+            }),
+            json!({
+                "asarUnpack": "**/*.node",
+                "neverUnpack": "**/foo/*.node",
+            }),
+            "test_assets".into(),
+        )?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_icons(false)
+            .build()
+            .proceed()?;
+        let unpacked = output_dir.join("resources/app.asar.unpacked/node_modules/ffi");
+        assert!(unpacked
+            .join("build/Release/ffi_bindings.node")
+            .is_file());
+        assert!(!unpacked.join("prebuilds/foo/ffi.node").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_values() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_from_values");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+            }),
+            "test_assets".into(),
+        )?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .build()
+            .proceed()?;
+        assert_eq!(
+            asar_files(&output_dir)?,
+            [
+                PathBuf::from("build/bundle.aoeuid.js"),
+                PathBuf::from("package.json"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_app_directory() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_app_directory");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "directories": {
+                    "app": "packages/desktop",
+                },
+                "files": ["**/*"],
+            }),
+            "test_assets".into(),
+        )?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .build()
+            .proceed()?;
+        assert_eq!(
+            asar_files(&output_dir)?,
+            [PathBuf::from("main.js"), PathBuf::from("package.json")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_follow_symlinks() -> Result<()> {
+        for follow in [true, false] {
+            let output_dir =
+                current_dir()?.join(format!(".test-workspace/pack_follow_symlinks_{follow}"));
+            let app = App::from_values(
+                json!({
+                    "name": "tasje",
+                    "version": "1.0.0",
+                }),
+                json!({
+                    "directories": {
+                        "app": "packages/symlinked",
+                    },
+                    "files": ["**/*"],
+                }),
+                "test_assets".into(),
+            )?;
+            PackingProcessBuilder::new(app)
+                .base_output_dir(&output_dir)
+                .follow_symlinks(follow)
+                .build()
+                .proceed()?;
+            assert_eq!(
+                asar_files(&output_dir)?.contains(&PathBuf::from("main.js")),
+                follow
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extra_resources_output_root() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_extra_resources_output_root");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "extraResources": [{
+                    "from": "build",
+                    "to": "${output}/",
+                }, {
+                    "from": "build",
+                    "to": "bundles",
+                }],
+            }),
+            "test_assets".into(),
+        )?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .build()
+            .proceed()?;
+        assert!(output_dir.join("bundle.aoeuid.js").is_file());
+        assert!(output_dir
+            .join("resources/bundles/bundle.aoeuid.js")
+            .is_file());
+
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "extraResources": {
+                    "from": "build",
+                    "to": "${output}/../escaped",
+                },
+            }),
+            "test_assets".into(),
+        )?;
+        assert!(PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .build()
+            .proceed()
+            .is_err());
+        assert!(!output_dir.with_file_name("escaped").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_asar_limits() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_asar_limits");
+        let limits = AsarLimits {
+            max_files: Some(1),
+            ..Default::default()
+        };
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        let summary = PackingProcessBuilder::new(app.clone())
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .asar_limits(limits)
+            .build()
+            .proceed()?;
+        assert_eq!(summary.asar_files, 3);
+        assert_eq!(
+            summary.warnings,
+            [
+                PackWarning::TooManyAsarFiles { count: 3, limit: 1 },
+                // from the test_assets desktop config, which passes any key through
+                PackWarning::NonstandardDesktopKey {
+                    key: "CustomField".to_string()
+                },
+            ]
+        );
+
+        assert!(PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .asar_limits(AsarLimits {
+                strict: true,
+                ..limits
+            })
+            .build()
+            .proceed()
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_asar() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_write_asar");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir)?;
+        }
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        let mut written = Vec::new();
+        let summary = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .build()
+            .write_asar(&mut written)?;
+        let asar = AsarReader::new(&written, None)?;
+        assert_eq!(asar.files().len(), summary.asar_files);
+        assert!(asar
+            .files()
+            .contains_key(Path::new("package.json")));
+        assert!(!output_dir.exists());
+
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "asar": false,
+            }),
+            "test_assets".into(),
+        )?;
+        assert!(PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .build()
+            .write_asar(Vec::new())
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reused_builder() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_reused_builder");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir)?;
+        }
+        let app = Arc::new(App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+                "linux": {
+                    "directories": { "output": output_dir.join("linux") },
+                },
+                "win": {
+                    "directories": { "output": output_dir.join("win") },
+                },
+            }),
+            "test_assets".into(),
+        )?);
+        let builder = PackingProcessBuilder::new(Arc::clone(&app))
+            .with_node_modules(false)
+            .with_icons(false);
+        for (platform, dir) in [(Platform::Linux, "linux"), (Platform::Windows, "win")] {
+            let process = builder
+                .clone()
+                .target_environment(Environment {
+                    architecture: HOST_ARCHITECTURE,
+                    platform,
+                })
+                .build();
+            assert_eq!(process.base_output_dir(), output_dir.join(dir));
+            process.proceed()?;
+            // the same process can be run again
+            process.proceed()?;
+            assert!(output_dir
+                .join(dir)
+                .join("resources/app.asar")
+                .is_file());
+        }
+        assert!(Arc::ptr_eq(&app, &builder.build().app));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_associations() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_empty_associations");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "build/bundle.aoeuid.js",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+                "protocols": {
+                    "name": "Tasje",
+                    "schemes": [],
+                },
+                "fileAssociations": {
+                    "ext": [],
+                },
+            }),
+            "test_assets".into(),
+        )?;
+        let builder = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .target_environment(Environment {
+                architecture: HOST_ARCHITECTURE,
+                platform: LINUX,
+            })
+            .with_node_modules(false)
+            .with_icons(false)
+            .dry_run(true);
+        let summary = builder.build().proceed()?;
+        assert_eq!(
+            summary.warnings,
+            [
+                PackWarning::EmptyAssociation {
+                    key: "protocols[0].schemes".to_string()
+                },
+                PackWarning::EmptyAssociation {
+                    key: "fileAssociations[0].ext".to_string()
+                },
+            ]
+        );
+        assert!(matches!(
+            builder
+                .strict_associations(true)
+                .build()
+                .proceed(),
+            Err(TasjeError::Strict(PackWarning::EmptyAssociation { .. }))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_largest_asar_files() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_largest_asar_files");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        let summary = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .dry_run(true)
+            .build()
+            .proceed()?;
+        let (largest_path, largest_size) = summary
+            .copy_lists
+            .asar
+            .iter()
+            .map(|(source, dest)| Ok((dest.clone(), fs::metadata(source)?.len())))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .max_by_key(|(_, size)| *size)
+            .unwrap();
+
+        let largest = summary.largest_asar_files(2)?;
+        assert_eq!(largest.len(), 2);
+        assert_eq!(largest[0].0, largest_path);
+        assert_eq!(largest[0].1, largest_size);
+        assert!(largest[0].1 >= largest[1].1);
+        assert_eq!(
+            summary.largest_asar_files(usize::MAX)?.len(),
+            summary.copy_lists.asar.len()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_atomic_output() -> Result<()> {
+        let workspace = current_dir()?.join(".test-workspace/pack_atomic_output");
+        if workspace.exists() {
+            fs::remove_dir_all(&workspace)?;
+        }
+        let output_dir = workspace.join("out");
+        fs::create_dir_all(&output_dir)?;
+        fs::write(output_dir.join("stale.txt"), "from a previous build")?;
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "build/bundle.aoeuid.js",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+                "extraResources": ["build/*"],
+            }),
+            "test_assets".into(),
+        )?;
+        let builder = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .with_icons(false)
+            .atomic_output(true);
+
+        // a failed build leaves the previous output alone
+        assert!(builder
+            .clone()
+            .presets(vec!["nonexistent".to_string()])
+            .build()
+            .proceed()
+            .is_err());
+        assert!(output_dir.join("stale.txt").is_file());
+
+        let summary = builder.build().proceed()?;
+        assert!(!output_dir.join("stale.txt").exists());
+        assert!(output_dir.join("resources/app.asar").is_file());
+        assert!(output_dir
+            .join("resources/build/bundle.aoeuid.js")
+            .is_file());
+        assert!(summary
+            .copy_lists
+            .extra
+            .iter()
+            .all(|(_, dest)| dest.starts_with(&output_dir)));
+        // nothing is left next to the output
+        let mut entries = fs::read_dir(&workspace)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort();
+        assert_eq!(entries, ["out"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_dry_run");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        let summary = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .dry_run(true)
+            .build()
+            .proceed()?;
+        assert!(summary.asar_files > 1);
+        assert!(!output_dir.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_main() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_missing_main");
+        for (main, missing) in [
+            ("index.js", true),
+            ("./build/bundle.aoeuid.js", false),
+            ("build/bundle.aoeuid", false),
+        ] {
+            let app = App::from_values(
+                json!({
+                    "name": "tasje",
+                    "version": "1.0.0",
+                    "main": main,
+                }),
+                json!({
+                    "files": ["build/**/*"],
+                }),
+                "test_assets".into(),
+            )?;
+            let summary = PackingProcessBuilder::new(app)
+                .base_output_dir(&output_dir)
+                .with_node_modules(false)
+                .dry_run(true)
+                .build()
+                .proceed()?;
+            assert_eq!(
+                summary
+                    .warnings
+                    .contains(&PackWarning::MissingMain {
+                        main: main.to_string()
+                    }),
+                missing
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_from() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_missing_from");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "build/bundle.aoeuid.js",
+            }),
+            json!({
+                "files": ["build/**/*"],
+                "extraResources": [{ "from": "missing-dir" }],
+            }),
+            "test_assets".into(),
+        )?;
+        let builder = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .dry_run(true);
+
+        let summary = builder.clone().build().proceed()?;
+        assert_eq!(
+            summary.warnings,
+            vec![PackWarning::MissingFrom {
+                from: PathBuf::from("test_assets/missing-dir")
+            }]
+        );
+        assert!(builder
+            .strict_from(true)
+            .build()
+            .proceed()
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unbounded_file_set() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_unbounded_file_set");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "build/bundle.aoeuid.js",
+            }),
+            json!({
+                "files": ["build/**/*"],
+                "extraResources": [{ "to": "everything" }, { "to": "js", "filter": "**/*.js" }],
+            }),
+            "test_assets".into(),
+        )?;
+        let builder = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .with_icons(false)
+            .dry_run(true);
+
+        let summary = builder.clone().build().proceed()?;
+        assert_eq!(
+            summary.warnings,
+            vec![PackWarning::UnboundedFileSet {
+                to: "everything".to_string()
+            }]
+        );
+        assert!(!summary.copy_lists.extra.is_empty());
+        assert!(summary
+            .copy_lists
+            .extra
+            .iter()
+            .all(|(_, dest)| dest.starts_with(output_dir.join("resources/js"))));
+        assert!(builder
+            .strict_from(true)
+            .build()
+            .proceed()
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_timings() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_timings");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        let summary = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .build()
+            .proceed()?;
+        let timings = summary.timings;
+        // icon optimization always takes some time
+        assert!(timings.icons > Duration::ZERO);
+        for phase in [
+            timings.walk,
+            timings.asar_write,
+            timings.unpack_copy,
+            timings.extra_copy,
+            timings.desktop,
+            timings.icons,
+        ] {
+            assert!(phase <= timings.total());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_file_rename() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_single_file_rename");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": {
+                    "from": "build/bundle.aoeuid.js",
+                    "to": "index.js",
+                },
+            }),
+            "test_assets".into(),
+        )?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .build()
+            .proceed()?;
+        let asar_file = fs::read(output_dir.join("resources/app.asar"))?;
+        let asar = AsarReader::new(&asar_file, None)?;
+        assert_eq!(
+            asar.files().keys().collect::<Vec<_>>(),
+            [Path::new("index.js"), Path::new("package.json")]
+        );
+        assert_eq!(
+            asar.read(Path::new("index.js")).unwrap().data(),
+            fs::read("test_assets/build/bundle.aoeuid.js")?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_keep_lockfiles() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_keep_lockfiles");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "directories": {
+                    "app": "packages/desktop",
+                },
+                "files": ["**/*"],
+                "keepLockfiles": true,
+            }),
+            "test_assets".into(),
+        )?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .build()
+            .proceed()?;
+        assert!(asar_files(&output_dir)?.contains(&PathBuf::from("package-lock.json")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_excludes() -> Result<()> {
+        let root = current_dir()?.join(".test-workspace/pack_default_excludes_app");
+        let output_dir = current_dir()?.join(".test-workspace/pack_default_excludes");
+        fs::create_dir_all(root.join("proto"))?;
+        fs::write(root.join("index.js"), "")?;
+        fs::write(root.join("proto/service.proto"), "")?;
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["**/*"],
+                "defaultExcludes": ["**/*.proto"],
+            }),
+            root,
+        )?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .with_icons(false)
+            .build()
+            .proceed()?;
+        let files = asar_files(&output_dir)?;
+        assert!(files.contains(&PathBuf::from("index.js")));
+        assert!(!files.contains(&PathBuf::from("proto/service.proto")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_dotfiles() -> Result<()> {
+        let root = current_dir()?.join(".test-workspace/pack_no_dotfiles_app");
+        let output_dir = current_dir()?.join(".test-workspace/pack_no_dotfiles");
+        fs::create_dir_all(root.join(".cache"))?;
+        fs::create_dir_all(root.join("config"))?;
+        fs::write(root.join("index.js"), "")?;
+        fs::write(root.join(".env"), "")?;
+        fs::write(root.join(".cache/data.json"), "")?;
+        fs::write(root.join("config/.settings"), "")?;
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["**/*", "config/.settings"],
+            }),
+            root.clone(),
+        )?;
+        for (dotfiles, packed) in [(true, true), (false, false)] {
+            PackingProcessBuilder::new(app.clone())
+                .base_output_dir(&output_dir)
+                .with_node_modules(false)
+                .with_icons(false)
+                .dotfiles(dotfiles)
+                .build()
+                .proceed()?;
+            let files = asar_files(&output_dir)?;
+            assert!(files.contains(&PathBuf::from("index.js")));
+            assert!(files.contains(&PathBuf::from("config/.settings")));
+            assert_eq!(files.contains(&PathBuf::from(".env")), packed);
+            assert_eq!(files.contains(&PathBuf::from(".cache/data.json")), packed);
+        }
+
+        // exclusions after the explicit dotfiles still win over them
+        fs::write(root.join(".npmrc"), "")?;
+        fs::write(root.join(".toolrc"), "")?;
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+            }),
+            json!({
+                "files": ["**/*.js", "**/.*rc", "!.npmrc"],
+            }),
+            root,
+        )?;
+        for dotfiles in [true, false] {
+            PackingProcessBuilder::new(app.clone())
+                .base_output_dir(&output_dir)
+                .with_node_modules(false)
+                .with_icons(false)
+                .dotfiles(dotfiles)
+                .build()
+                .proceed()?;
+            let files = asar_files(&output_dir)?;
+            assert!(files.contains(&PathBuf::from(".toolrc")));
+            assert!(!files.contains(&PathBuf::from(".npmrc")), "{dotfiles}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tasje_options() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_tasje_options");
+        for (standard_filters, packed) in [(None, false), (Some(false), true)] {
+            let app = App::from_values(
+                json!({
+                    "name": "tasje",
+                    "version": "1.0.0",
+                    "tasje": {
+                        "standardFilters": standard_filters,
+                    },
+                }),
+                json!({
+                    "files": ["**/*"],
+                }),
+                "test_assets".into(),
+            )?;
+            PackingProcessBuilder::new(app)
+                .base_output_dir(&output_dir)
+                .with_node_modules(false)
+                .with_icons(false)
+                .build()
+                .proceed()?;
+            let files = asar_files(&output_dir)?;
+            assert_eq!(files.contains(&PathBuf::from("docs/guide.md")), packed);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_metadata_only() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_metadata_only");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir)?;
+        }
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        let summary = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .target_environment(Environment {
+                architecture: HOST_ARCHITECTURE,
+                platform: LINUX,
+            })
+            .with_resources(false)
+            .build()
+            .proceed()?;
+        assert_eq!(summary.asar_files, 0);
+        let mut entries = fs::read_dir(&output_dir)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort();
+        assert_eq!(
+            entries,
+            [
+                "electron_tasje.desktop",
+                "electron_tasje.metainfo.xml",
+                "icons"
+            ]
+        );
+        assert!(fs::read_dir(output_dir.join("icons"))?
+            .next()
+            .is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_icons() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_no_icons");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_icons(false)
+            .build()
+            .proceed()?;
+        assert!(output_dir.join("resources/app.asar").is_file());
+        assert!(!output_dir.join("icons").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_dir_relative_to_root() -> Result<()> {
+        let app = App::new_from_files(
+            "test_assets/package.json",
+            "test_assets/config/electron-builder.json",
+        )?;
+        assert_eq!(app.output_dir(LINUX), Path::new("test_assets/dist_out"));
+        let process = PackingProcessBuilder::new(app.clone())
+            .target_environment(Environment {
+                architecture: HOST_ARCHITECTURE,
+                platform: LINUX,
+            })
+            .build();
+        assert_eq!(process.base_output_dir(), Path::new("test_assets/dist_out"));
+
+        let process = PackingProcessBuilder::new(app)
+            .base_output_dir("other_out")
+            .build();
+        assert_eq!(
+            process.base_output_dir(),
+            Path::new("test_assets/other_out")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_absolute_output_dir() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_absolute_output_dir");
+        if output_dir.exists() {
+            fs::remove_dir_all(&output_dir)?;
+        }
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "build/bundle.aoeuid.js",
+            }),
+            json!({
+                "files": ["/build/**/*"],
+                "directories": {
+                    "output": output_dir.join("from_config"),
+                },
+            }),
+            "test_assets".into(),
+        )?;
+        assert_eq!(app.output_dir(LINUX), output_dir.join("from_config"));
+        let process = PackingProcessBuilder::new(app.clone())
+            .with_node_modules(false)
+            .with_icons(false)
+            .build();
+        assert_eq!(process.base_output_dir(), output_dir.join("from_config"));
+        process.proceed()?;
+        assert!(output_dir
+            .join("from_config/resources/app.asar")
+            .is_file());
+
+        PackingProcessBuilder::new(app)
+            .base_output_dir(output_dir.join("from_builder"))
+            .with_node_modules(false)
+            .with_icons(false)
+            .build()
+            .proceed()?;
+        assert!(output_dir
+            .join("from_builder/resources/app.asar")
+            .is_file());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_kinds() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_error_kinds");
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "missing.js",
+            }),
+            json!({
+                "files": ["build/**/*"],
+                "integrityAlgorithm": "md5",
+            }),
+            "test_assets".into(),
+        )?;
+        let result = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .dry_run(true)
+            .build()
+            .proceed();
+        assert!(matches!(result, Err(TasjeError::Config(_))));
+
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "missing.js",
+            }),
+            json!({
+                "files": ["build/**/*"],
+            }),
+            "test_assets".into(),
+        )?;
+        let result = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .with_node_modules(false)
+            .dry_run(true)
+            .strict_main(true)
+            .build()
+            .proceed();
+        assert!(matches!(
+            result,
+            Err(TasjeError::Strict(PackWarning::MissingMain { .. }))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_error() {
+        let path = Path::new("app/secret.js");
+        let error = TasjeError::classify(
+            read_error(path, io::Error::from(ErrorKind::PermissionDenied)),
+            TasjeError::Asar,
+        );
+        assert!(matches!(error, TasjeError::PermissionDenied { .. }));
+        assert!(error
+            .to_string()
+            .contains("secret.js\" is not readable"));
+        let error = TasjeError::classify(
+            read_error(path, io::Error::from(ErrorKind::NotFound)),
+            TasjeError::Asar,
+        );
+        assert!(matches!(error, TasjeError::FileNotFound { .. }));
+        let error = TasjeError::classify(
+            read_error(path, io::Error::from(ErrorKind::InvalidData)),
+            TasjeError::Asar,
+        );
+        assert!(matches!(error, TasjeError::Asar(_)));
+        assert!(error
+            .to_string()
+            .contains("reading \"app/secret.js\""));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_file() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let workspace = current_dir()?.join(".test-workspace/pack_unreadable_file");
+        let root = workspace.join("app");
+        fs::create_dir_all(&root)?;
+        let secret = root.join("secret.js");
+        fs::write(&secret, "")?;
+        fs::set_permissions(&secret, fs::Permissions::from_mode(0o000))?;
+        // root can read it anyway, test_read_error covers the error then
+        if fs::read(&secret).is_ok() {
+            return Ok(());
+        }
+        let app = App::from_values(
+            json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "main": "secret.js",
+            }),
+            json!({
+                "files": ["secret.js"],
+            }),
+            root,
+        )?;
+        let result = PackingProcessBuilder::new(app)
+            .base_output_dir(workspace.join("output"))
+            .with_node_modules(false)
+            .with_icons(false)
+            .build()
+            .proceed();
+        fs::set_permissions(&secret, fs::Permissions::from_mode(0o644))?;
+        let error = result.unwrap_err();
+        assert!(matches!(error, TasjeError::PermissionDenied { .. }));
+        assert!(error
+            .to_string()
+            .contains("secret.js\" is not readable"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_integrity_algorithm() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_integrity_algorithm");
+        for (algorithm, block_size, supported) in [
+            ("sha256", 4 * 1024 * 1024, true),
+            ("SHA256", 4 * 1024 * 1024, true),
+            ("sha512", 4 * 1024 * 1024, false),
+            ("sha256", 1024 * 1024, false),
+        ] {
+            let app = App::from_values(
+                json!({
+                    "name": "tasje",
+                    "version": "1.0.0",
+                }),
+                json!({
+                    "files": ["build/**/*"],
+                    "integrityAlgorithm": algorithm,
+                    "integrityBlockSize": block_size,
+                }),
+                "test_assets".into(),
+            )?;
+            let result = PackingProcessBuilder::new(app)
+                .base_output_dir(&output_dir)
+                .with_node_modules(false)
+                .with_icons(false)
+                .build()
+                .proceed();
+            assert_eq!(result.is_ok(), supported);
+        }
+
+        let asar_file = fs::read(output_dir.join("resources/app.asar"))?;
+        let asar = AsarReader::new(&asar_file, None)?;
+        let integrity = asar
+            .read(Path::new("build/bundle.aoeuid.js"))
+            .unwrap()
+            .integrity()
+            .unwrap();
+        assert_eq!(integrity.algorithm(), HashAlgorithm::Sha256);
+        assert_eq!(integrity.hash().len(), 32);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jobs() -> Result<()> {
+        let output_dir = current_dir()?.join(".test-workspace/pack_jobs");
+        let app = App::new_from_package_file("test_assets/package.json")?;
+        let process = PackingProcessBuilder::new(app)
+            .base_output_dir(&output_dir)
+            .jobs(1)
+            .build();
+        let thread_ids = process.thread_pool()?.install(|| {
+            (0..64)
+                .into_par_iter()
+                .map(|_| thread::current().id())
+                .collect::<HashSet<_>>()
+        });
+        assert_eq!(thread_ids.len(), 1);
+
+        process.proceed()?;
+        let files = asar_files(&output_dir)?;
+        assert!(files.contains(&PathBuf::from("node_modules/tasje_dep/index.js")));
+        assert!(files.contains(&PathBuf::from("cuild/bundle.aoeuid.js")));
+
+        Ok(())
     }
 }
@@ -1,5 +1,5 @@
 use crate::config::{CommonOverridableProperties, EBuilderConfig};
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use serde_json::Value;
 
 #[derive(Debug, Clone, Deserialize)]
@@ -7,9 +7,100 @@ use serde_json::Value;
 pub struct PackageManifest {
     pub name: String,
     pub version: String,
+    pub license: Option<PackageLicense>,
+    #[serde(default, deserialize_with = "lenient_author")]
+    pub author: Option<PackageAuthor>,
+    pub main: Option<String>,
     #[serde(flatten)]
     pub common: CommonOverridableProperties,
     pub build: Option<EBuilderConfig>,
+    #[serde(default)]
+    pub tasje: TasjeOptions,
+}
+
+/// options specific to tasje, from the "tasje" key of package.json,
+/// which are not a part of the electron-builder config schema
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TasjeOptions {
+    /// whether to leave out the usual junk files (docs, tests, lockfiles etc.), defaults to true
+    pub standard_filters: Option<bool>,
+    /// whether to cache the optimized icons in the default cache dir, defaults to true
+    pub icon_cache: Option<bool>,
+}
+
+/// https://docs.npmjs.com/cli/configuring-npm/package-json#license
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum PackageLicense {
+    Expression(String),
+    /// deprecated, but still found in the wild
+    Object {
+        #[serde(rename = "type")]
+        kind: String,
+    },
+}
+
+impl PackageLicense {
+    pub fn expression(&self) -> &str {
+        match self {
+            PackageLicense::Expression(e) => e,
+            PackageLicense::Object { kind } => kind,
+        }
+    }
+}
+
+/// https://docs.npmjs.com/cli/configuring-npm/package-json#people-fields-author-contributors
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum PackageAuthor {
+    /// like `Name <email> (url)`, with both email and url optional
+    Short(String),
+    Object {
+        name: Option<String>,
+        email: Option<String>,
+        url: Option<String>,
+    },
+}
+
+/// npm accepts more shapes of authors than are understood here, which are ignored
+/// instead of failing to read the whole package.json
+fn lenient_author<'de, D>(deserializer: D) -> Result<Option<PackageAuthor>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(serde_json::from_value(Value::deserialize(deserializer)?).ok())
+}
+
+/// the part of the short form between `open` and `close`
+fn delimited(author: &str, open: char, close: char) -> Option<&str> {
+    let (_, rest) = author.split_once(open)?;
+    let (inner, _) = rest.split_once(close)?;
+    Some(inner.trim()).filter(|i| !i.is_empty())
+}
+
+impl PackageAuthor {
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            PackageAuthor::Short(author) => author.split(['<', '(']).next().map(str::trim),
+            PackageAuthor::Object { name, .. } => name.as_deref().map(str::trim),
+        }
+        .filter(|name| !name.is_empty())
+    }
+
+    pub fn email(&self) -> Option<&str> {
+        match self {
+            PackageAuthor::Short(author) => delimited(author, '<', '>'),
+            PackageAuthor::Object { email, .. } => email.as_deref(),
+        }
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            PackageAuthor::Short(author) => delimited(author, '(', ')'),
+            PackageAuthor::Object { url, .. } => url.as_deref(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -26,3 +117,59 @@ impl TryFrom<Value> for Package {
         Ok(Package { value, manifest })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Package, PackageAuthor};
+    use anyhow::Result;
+    use serde_json::json;
+
+    #[test]
+    fn test_author() -> Result<()> {
+        let author: PackageAuthor = serde_json::from_value(json!(
+            "lauren n. liberda <lauren@selfisekai.rocks> (https://selfisekai.rocks)"
+        ))?;
+        assert_eq!(author.name(), Some("lauren n. liberda"));
+        assert_eq!(author.email(), Some("lauren@selfisekai.rocks"));
+        assert_eq!(author.url(), Some("https://selfisekai.rocks"));
+
+        let author: PackageAuthor = serde_json::from_value(json!("Example (https://example.com)"))?;
+        assert_eq!(author.name(), Some("Example"));
+        assert_eq!(author.email(), None);
+        assert_eq!(author.url(), Some("https://example.com"));
+
+        let author: PackageAuthor = serde_json::from_value(json!({
+            "name": "Example",
+            "email": "example@example.com",
+        }))?;
+        assert_eq!(author.name(), Some("Example"));
+        assert_eq!(author.email(), Some("example@example.com"));
+        assert_eq!(author.url(), None);
+
+        let author: PackageAuthor = serde_json::from_value(json!({
+            "email": "example@example.com",
+        }))?;
+        assert_eq!(author.name(), None);
+        assert_eq!(author.email(), Some("example@example.com"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_author() -> Result<()> {
+        for author in [
+            json!(42),
+            json!(["Example"]),
+            json!({ "name": ["Example"] }),
+        ] {
+            let package = Package::try_from(json!({
+                "name": "tasje",
+                "version": "1.0.0",
+                "author": author,
+            }))?;
+            assert!(package.manifest.author.is_none());
+        }
+
+        Ok(())
+    }
+}
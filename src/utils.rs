@@ -1,11 +1,170 @@
 use crate::environment::Environment;
 use anyhow::{bail, Context, Result};
+use deunicode::deunicode;
 use once_cell::sync::Lazy;
 use regex::{Captures, Regex};
+use serde::de::{DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
 use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 static TEMPLATE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\$\{([a-zA-Z_. ]+)\}").unwrap());
 
+/// walks a document like a config file, collecting the dotted paths of keys
+/// that appear more than once in the same mapping. serde keeps just the last value
+struct DuplicateKeysSeed<'a> {
+    path: String,
+    duplicates: &'a mut Vec<String>,
+}
+
+impl<'a> DuplicateKeysSeed<'a> {
+    fn nested(&mut self, key: &str) -> DuplicateKeysSeed<'_> {
+        DuplicateKeysSeed {
+            path: if self.path.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{key}", self.path)
+            },
+            duplicates: self.duplicates,
+        }
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for DuplicateKeysSeed<'a> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for DuplicateKeysSeed<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let mut seen = HashSet::new();
+        while let Some(key) = map.next_key::<KeyName>()? {
+            if !seen.insert(key.0.clone()) {
+                let path = self.nested(&key.0).path;
+                self.duplicates.push(path);
+            }
+            map.next_value_seed(self.nested(&key.0))?;
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        let mut i = 0;
+        while seq
+            .next_element_seed(self.nested(&i.to_string()))?
+            .is_some()
+        {
+            i += 1;
+        }
+        Ok(())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_none<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// mapping keys, which in yaml are not always strings
+struct KeyName(String);
+
+impl<'de> Deserialize<'de> for KeyName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyVisitor;
+
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = KeyName;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a scalar key")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<KeyName, E> {
+                Ok(KeyName(v.to_string()))
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<KeyName, E> {
+                Ok(KeyName(v.to_string()))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<KeyName, E> {
+                Ok(KeyName(v.to_string()))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<KeyName, E> {
+                Ok(KeyName(v.to_string()))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<KeyName, E> {
+                Ok(KeyName(v.to_string()))
+            }
+
+            fn visit_unit<E>(self) -> Result<KeyName, E> {
+                Ok(KeyName("null".to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(KeyVisitor)
+    }
+}
+
+/// dotted paths of the keys defined more than once, like `linux.category`
+pub(crate) fn duplicate_keys<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    let mut duplicates = Vec::new();
+    DuplicateKeysSeed {
+        path: String::new(),
+        duplicates: &mut duplicates,
+    }
+    .deserialize(deserializer)?;
+    Ok(duplicates)
+}
+
 pub(crate) fn try_flatten<S, T>(iter: S) -> Result<Vec<T>>
 where
     S: Iterator<Item = Result<T>>,
@@ -20,6 +179,148 @@ where
     Ok(unwrapped)
 }
 
+/// creates directories, remembering which ones already exist,
+/// to skip redundant syscalls when copying many files into the same directories
+#[derive(Debug, Default)]
+pub(crate) struct DirectoryCreator {
+    created: Mutex<HashSet<PathBuf>>,
+}
+
+/// a clone starts with nothing remembered, as it might be used for other directories
+impl Clone for DirectoryCreator {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl DirectoryCreator {
+    pub(crate) fn create_dir_all<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let dir = dir.as_ref();
+        // holding the lock while creating, so no other thread tries the same dir meanwhile
+        let mut created = self.created.lock().unwrap();
+        if created.contains(dir) {
+            return Ok(());
+        }
+        fs::create_dir_all(dir).with_context(|| format!("on creating directory: {dir:?}"))?;
+        for ancestor in dir.ancestors() {
+            if !created.insert(ancestor.to_path_buf()) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// creates the parent directory of a file
+    pub(crate) fn create_parent<P: AsRef<Path>>(&self, file: P) -> Result<()> {
+        match file.as_ref().parent() {
+            Some(parent) => self.create_dir_all(parent),
+            None => Ok(()),
+        }
+    }
+}
+
+/// writes to a temporary file next to `path`, and moves it into place only if writing succeeded,
+/// so that a failure never leaves a truncated file behind
+pub(crate) fn write_atomically<P, F>(path: P, write: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut File) -> Result<()>,
+{
+    let path = path.as_ref();
+    let mut temp_name = OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = File::create(&temp_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            file.sync_all()?;
+            Ok(())
+        })
+        .and_then(|_| Ok(fs::rename(&temp_path, path)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result.with_context(|| format!("on writing file: {path:?}"))
+}
+
+/// a hidden sibling of `path`, like `.dist.staging` for `dist`
+pub(crate) fn hidden_sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// moves the `staged` directory into place of `target`, removing the previous `target`.
+/// `target` is missing for a moment in between, but is never seen incomplete
+pub(crate) fn replace_dir(staged: &Path, target: &Path) -> Result<()> {
+    let previous = hidden_sibling(target, "previous");
+    if previous.exists() {
+        fs::remove_dir_all(&previous)
+            .with_context(|| format!("on removing directory: {previous:?}"))?;
+    }
+    if target.exists() {
+        fs::rename(target, &previous)
+            .with_context(|| format!("on moving {target:?} to {previous:?}"))?;
+    }
+    match fs::rename(staged, target) {
+        Ok(()) => {}
+        // siblings are on the same filesystem, unless one of them is a mount point
+        Err(err) if err.kind() == ErrorKind::CrossesDevices => {
+            copy_dir(staged, target)?;
+            fs::remove_dir_all(staged)
+                .with_context(|| format!("on removing directory: {staged:?}"))?;
+        }
+        Err(err) => {
+            // putting the previous output back, so that it's not lost
+            if previous.exists() {
+                let _ = fs::rename(&previous, target);
+            }
+            return Err(err).with_context(|| format!("on moving {staged:?} to {target:?}"));
+        }
+    }
+    if previous.exists() {
+        fs::remove_dir_all(&previous)
+            .with_context(|| format!("on removing directory: {previous:?}"))?;
+    }
+    Ok(())
+}
+
+fn copy_dir(source: &Path, target: &Path) -> Result<()> {
+    for entry in walkdir::WalkDir::new(source) {
+        let entry = entry?;
+        let dest = target.join(entry.path().strip_prefix(source)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&dest)
+                .with_context(|| format!("on creating directory: {dest:?}"))?;
+        } else {
+            fs::copy(entry.path(), &dest)
+                .with_context(|| format!("on copying {:?} to {dest:?}", entry.path()))?;
+        }
+    }
+    Ok(())
+}
+
+/// whether `dest` exists with the same contents as `source`, so copying can be skipped
+pub(crate) fn same_contents<P, Q>(source: P, dest: Q) -> Result<bool>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let dest_meta = match fs::metadata(&dest) {
+        Ok(meta) => meta,
+        Err(_) => return Ok(false),
+    };
+    if !dest_meta.is_file() || fs::metadata(&source)?.len() != dest_meta.len() {
+        return Ok(false);
+    }
+    Ok(Sha256::digest(fs::read(source)?) == Sha256::digest(fs::read(dest)?))
+}
+
 /// from regex crate docs
 fn replace_all<E>(
     re: &Regex,
@@ -38,6 +339,65 @@ fn replace_all<E>(
     Ok(new)
 }
 
+/// like [`filesafe_package_name`], but converts non-ASCII characters to their closest
+/// ASCII representation first, instead of rejecting them
+pub fn transliterated_package_name(name: &str) -> Result<String> {
+    filesafe_package_name(&deunicode(name))
+        .with_context(|| format!("on transliterating package name: {:?}", name))
+}
+
+/// the year of a unix timestamp, in UTC
+pub(crate) fn year_from_unix(secs: i64) -> i64 {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = secs.div_euclid(86400) + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let year = yoe + era * 400;
+    if mp >= 10 {
+        year + 1
+    } else {
+        year
+    }
+}
+
+/// the current year, or the one of SOURCE_DATE_EPOCH for reproducible builds
+pub(crate) fn current_year() -> Result<i64> {
+    let secs = match env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch
+            .parse()
+            .with_context(|| format!("invalid SOURCE_DATE_EPOCH: {epoch:?}"))?,
+        Err(_) => SystemTime::now()
+            .duration_since(UNIX_EPOCH)?
+            .as_secs() as i64,
+    };
+    Ok(year_from_unix(secs))
+}
+
+/// `${year}` and `${author}` in the copyright, like electron-builder does
+pub(crate) fn fill_copyright_template(
+    template: &str,
+    year: i64,
+    author: Option<&str>,
+) -> Result<String> {
+    replace_all(
+        &TEMPLATE_REGEX,
+        template,
+        |captures: &Captures| -> Result<String> {
+            match captures.get(1).unwrap().as_str().trim() {
+                "year" => Ok(year.to_string()),
+                "author" => author
+                    .map(str::to_string)
+                    .context("${author} is used in the copyright, but package.json has no author"),
+                // left for whoever reads the copyright, like before templates were filled
+                _ => Ok(captures.get(0).unwrap().as_str().to_string()),
+            }
+        },
+    )
+}
+
 pub(crate) fn fill_variable_template<S: AsRef<str>>(
     template: S,
     environment: Environment,
@@ -63,6 +423,31 @@ pub(crate) fn fill_variable_template<S: AsRef<str>>(
     )
 }
 
+/// loads variables for `${env.*}` templates from a .env file.
+/// variables already set in the environment take precedence
+pub fn load_env_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    dotenvy::from_path(path).with_context(|| format!("on loading env file: {path:?}"))?;
+    Ok(())
+}
+
+/// where the optimized icons are cached between builds by default
+pub fn default_icon_cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .map(|cache| cache.join("tasje"))
+}
+
+/// expands a leading `~/` to the home directory, like shells do
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) if !home.is_empty() => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
 pub fn filesafe_package_name(name: &str) -> Result<String> {
     let new = name.replace('@', "").replace('/', "-");
     if new
@@ -76,9 +461,17 @@ pub fn filesafe_package_name(name: &str) -> Result<String> {
 
 #[cfg(test)]
 mod tests {
-    use super::{filesafe_package_name, fill_variable_template};
+    use super::{
+        current_year, expand_home, filesafe_package_name, fill_copyright_template,
+        fill_variable_template, load_env_file, transliterated_package_name, write_atomically,
+        year_from_unix, DirectoryCreator,
+    };
     use crate::environment::Environment;
-    use anyhow::Result;
+    use anyhow::{bail, Result};
+    use std::fs::{create_dir_all, read_dir, read_to_string, write};
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::thread;
 
     #[test]
     fn test_variable_templates() -> Result<()> {
@@ -99,6 +492,64 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_copyright_template() -> Result<()> {
+        assert_eq!(year_from_unix(0), 1970);
+        assert_eq!(year_from_unix(1704067199), 2023);
+        assert_eq!(year_from_unix(1704067200), 2024);
+        assert_eq!(year_from_unix(951782400), 2000);
+        assert!(current_year()? >= 2024);
+
+        assert_eq!(
+            fill_copyright_template("© ${year} ${author}", 2024, Some("Example"))?,
+            "© 2024 Example"
+        );
+        assert!(fill_copyright_template("© ${author}", 2024, None).is_err());
+        assert_eq!(
+            fill_copyright_template("© ${arch} ${year}", 2024, None)?,
+            "© ${arch} 2024"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let home = PathBuf::from(std::env::var_os("HOME").unwrap());
+        assert_eq!(expand_home("~/x"), home.join("x"));
+        assert_eq!(expand_home("~other/x"), PathBuf::from("~other/x"));
+        assert_eq!(expand_home("assets/~/x"), PathBuf::from("assets/~/x"));
+        assert_eq!(expand_home("/x"), PathBuf::from("/x"));
+    }
+
+    #[test]
+    fn test_env_file() -> Result<()> {
+        let env = Environment {
+            architecture: crate::environment::Architecture::Aarch64,
+            platform: crate::environment::Platform::Linux,
+        };
+        let dir = std::env::current_dir()?.join(".test-workspace/env_file");
+        create_dir_all(&dir)?;
+        write(
+            dir.join(".env"),
+            "TASJE_TEST_DOTENV_DIR=build\nTASJE_TEST_DOTENV_REAL=from_file\n",
+        )?;
+        std::env::set_var("TASJE_TEST_DOTENV_REAL", "from_env");
+
+        load_env_file(dir.join(".env"))?;
+        assert_eq!(
+            fill_variable_template("${env.TASJE_TEST_DOTENV_DIR}/**/*.js", env)?,
+            "build/**/*.js"
+        );
+        assert_eq!(
+            fill_variable_template("${env.TASJE_TEST_DOTENV_REAL}", env)?,
+            "from_env"
+        );
+        assert!(load_env_file(dir.join("missing.env")).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_filesafe_name() -> Result<()> {
         assert_eq!(filesafe_package_name("tasje")?, "tasje");
@@ -109,4 +560,63 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_transliterated_name() -> Result<()> {
+        assert!(filesafe_package_name("@corp/приложение").is_err());
+        assert_eq!(
+            transliterated_package_name("@corp/приложение")?,
+            "corp-prilozhenie"
+        );
+        assert_eq!(transliterated_package_name("tasje")?, "tasje");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_atomically() -> Result<()> {
+        let dir = Path::new(".test-workspace/write_atomically");
+        create_dir_all(dir)?;
+        let target = dir.join("app.asar");
+
+        write_atomically(&target, |file| Ok(file.write_all(b"tasje")?))?;
+        assert_eq!(read_to_string(&target)?, "tasje");
+
+        let failing = dir.join("failing.asar");
+        assert!(write_atomically(&failing, |file| {
+            file.write_all(b"tas")?;
+            bail!("disk full");
+        })
+        .is_err());
+        assert!(!failing.exists());
+        assert_eq!(read_dir(dir)?.count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_directory_creator() -> Result<()> {
+        let dir = Path::new(".test-workspace/directory_creator/some/deep/directory");
+        let creator = DirectoryCreator::default();
+        thread::scope(|scope| -> Result<()> {
+            let handles = (0..16)
+                .map(|i| {
+                    let creator = &creator;
+                    scope.spawn(move || -> Result<()> {
+                        let file = dir.join(format!("{i}.js"));
+                        creator.create_parent(&file)?;
+                        write(file, "tasje")?;
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+            for handle in handles {
+                handle.join().unwrap()?;
+            }
+            Ok(())
+        })?;
+        assert_eq!(read_dir(dir)?.count(), 16);
+
+        Ok(())
+    }
 }
@@ -1,21 +1,117 @@
 use crate::config::{CopyDef, FileSet};
 use crate::environment::Environment;
-use crate::utils::{fill_variable_template, try_flatten};
-use anyhow::Result;
+use crate::pack::OUTPUT_PLACEHOLDER;
+use crate::utils::{expand_home, fill_variable_template, try_flatten};
+use anyhow::{Context, Result};
 use globreeks::Globreeks;
+use std::fs;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use std::vec::IntoIter;
 use walkdir::WalkDir;
 
+/// in a set's `to`, replaced with the file name of each matched file,
+/// which allows flattening the matched files into one directory
+const BASENAME_VARIABLE: &str = "${basename}";
+
+/// like in electron-builder, a leading slash anchors the glob to the root,
+/// while globs without it match at any depth
+fn anchored_glob(glob: String) -> String {
+    let (negation, pattern) = match glob.strip_prefix('!') {
+        Some(pattern) => ("!", pattern),
+        None => ("", glob.as_str()),
+    };
+    if let Some(anchored) = pattern.strip_prefix('/') {
+        format!("{negation}{anchored}")
+    } else if pattern.starts_with("**") {
+        glob
+    } else {
+        format!("{negation}**/{pattern}")
+    }
+}
+
+/// only files are matched, so a directory pattern like `node_modules/sharp`
+/// has to match everything beneath it, too
+fn with_descendants(glob: &str) -> [String; 2] {
+    let glob = glob.trim_end_matches('/');
+    [glob.to_string(), format!("{glob}/**")]
+}
+
+/// matches what `globs` unpack from the asar, anchored like the globs of "files",
+/// together with everything beneath the matched directories
+pub(crate) fn unpack_matcher<I>(globs: I) -> Result<Globreeks>
+where
+    I: IntoIterator<Item = String>,
+{
+    Globreeks::new(
+        globs
+            .into_iter()
+            .map(anchored_glob)
+            .flat_map(|g| with_descendants(&g)),
+    )
+}
+
+/// negated globs of paths marked with `export-ignore` in `.gitattributes` in the root,
+/// which are not meant to be distributed, like in `git archive`
+pub(crate) fn export_ignore_globs(root: &Path) -> Result<Vec<String>> {
+    let attributes = match fs::read_to_string(root.join(".gitattributes")) {
+        Ok(attributes) => attributes,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut globs = Vec::new();
+    for line in attributes.lines() {
+        let mut parts = line.split_whitespace();
+        let Some(pattern) = parts.next().filter(|p| !p.starts_with('#')) else {
+            continue;
+        };
+        // `-export-ignore` and `!export-ignore` unset it
+        if !parts.any(|attr| attr == "export-ignore") {
+            continue;
+        }
+        // like in .gitignore, patterns with a slash in the middle are relative to the root
+        let pattern = pattern.trim_end_matches('/');
+        let glob = if pattern.contains('/') && !pattern.starts_with('/') {
+            format!("/{pattern}")
+        } else {
+            pattern.to_string()
+        };
+        for glob in with_descendants(&glob) {
+            globs.push(format!("!{glob}"));
+        }
+    }
+    Ok(globs)
+}
+
+/// fills the variables in a set's `to`, except for `${basename}`,
+/// which is different for every file, and so is filled when walking,
+/// and `${output}`, which is resolved when copying
+fn fill_to_template(to: &str, environment: Environment) -> Result<String> {
+    let fill = |part: &str| -> Result<String> {
+        Ok(try_flatten(
+            part.split(OUTPUT_PLACEHOLDER)
+                .map(|part| fill_variable_template(part, environment)),
+        )?
+        .join(OUTPUT_PLACEHOLDER))
+    };
+    Ok(try_flatten(to.split(BASENAME_VARIABLE).map(fill))?.join(BASENAME_VARIABLE))
+}
+
 #[derive(Debug)]
 pub(crate) struct Walker<'a> {
     root: PathBuf,
+    /// where the current walk started, which is outside of the root for `from` in home
+    walk_root: PathBuf,
     globs: Globreeks,
-    sets: IntoIter<(&'a FileSet, Vec<String>)>,
+    /// with the matcher of each set, compiled upfront
+    sets: IntoIter<(&'a FileSet, Globreeks, Option<String>)>,
     current_set: Option<&'a FileSet>,
+    /// `to` of the current set, with the variables filled
+    current_to: Option<String>,
     current_walk: walkdir::IntoIter,
     done_with_globs: bool,
     unpack_globs: Option<Globreeks>,
+    follow_links: bool,
 }
 
 impl<'a> Walker<'a> {
@@ -24,6 +120,8 @@ impl<'a> Walker<'a> {
         environment: Environment,
         to_copy: Vec<&'a CopyDef>,
         unpack_list: Option<Vec<&String>>,
+        never_unpack: Vec<&String>,
+        follow_links: bool,
     ) -> Result<Self> {
         let mut globs = Vec::new();
         let mut sets = Vec::new();
@@ -36,36 +134,64 @@ impl<'a> Walker<'a> {
 
         Ok(Self {
             root: root.clone(),
+            walk_root: root.clone(),
             globs: Globreeks::new(try_flatten(
                 globs
                     .iter()
-                    .map(|f| fill_variable_template(f, environment)),
+                    .map(|f| fill_variable_template(f, environment).map(anchored_glob)),
             )?)?,
             sets: try_flatten(sets.into_iter().map(|s| {
+                let mut filters = try_flatten(
+                    s.filters()
+                        .iter()
+                        .map(|f| fill_variable_template(f, environment).map(anchored_glob)),
+                )?;
+                // only exclusions, which are applied to everything in `from`
+                if !filters.iter().any(|f| !f.starts_with('!')) {
+                    filters.insert(0, "**/*".to_string());
+                }
                 Ok((
                     s,
-                    try_flatten(
-                        s.filters()
-                            .iter()
-                            .map(|f| fill_variable_template(f, environment)),
-                    )?,
+                    Globreeks::new(filters).with_context(|| {
+                        format!(
+                            "invalid filter in the file set from {:?}",
+                            s.from().unwrap_or_default()
+                        )
+                    })?,
+                    s.to()
+                        .map(|to| fill_to_template(to, environment))
+                        .transpose()?,
                 ))
             }))?
             .into_iter(),
             current_set: None,
-            current_walk: WalkDir::new(root).follow_links(true).into_iter(),
+            current_to: None,
+            current_walk: WalkDir::new(root)
+                .follow_links(follow_links)
+                .into_iter(),
             done_with_globs: globs.is_empty(),
+            // the last matching glob wins, so these take precedence over the unpack list
             unpack_globs: if let Some(gl) = unpack_list {
-                Some(Globreeks::new(gl)?)
+                Some(unpack_matcher(
+                    gl.into_iter()
+                        .cloned()
+                        .chain(never_unpack.into_iter().map(|g| format!("!{g}"))),
+                )?)
             } else {
                 None
             },
+            follow_links,
         })
     }
 
+    /// without following links, symlinks are not files, so they are skipped.
+    /// when following, broken symlinks fail to resolve and are skipped as well
     fn next_current_walk(&mut self) -> Option<(PathBuf, bool)> {
         for direntry in self.current_walk.by_ref().flatten() {
-            let path = direntry.path().strip_prefix(&self.root).unwrap();
+            let path = direntry
+                .path()
+                .strip_prefix(&self.walk_root)
+                .unwrap();
             let path_cand = globreeks::Candidate::new(path);
             if self.globs.evaluate_candidate(&path_cand) && direntry.file_type().is_file() {
                 let unpack = self
@@ -88,7 +214,7 @@ impl<'a> Iterator for Walker<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         if !self.done_with_globs {
             if let Some((path, unpack)) = self.next_current_walk() {
-                return Some((self.root.join(&path), path, unpack));
+                return Some((self.walk_root.join(&path), path, unpack));
             }
             self.done_with_globs = true;
         }
@@ -96,34 +222,53 @@ impl<'a> Iterator for Walker<'a> {
         loop {
             if let Some(set) = self.current_set {
                 if let Some((path, unpack)) = self.next_current_walk() {
+                    // paths are relative to the walk root, which already is `from` if it's in home
+                    let from = if self.walk_root == self.root {
+                        set.from().unwrap_or_default()
+                    } else {
+                        ""
+                    };
                     return Some((
-                        self.root.join(&path),
-                        set.to()
+                        self.walk_root.join(&path),
+                        self.current_to
+                            .as_ref()
                             .map(|to| {
-                                Path::new(&to).join(
-                                    path.strip_prefix(set.from().unwrap_or_default())
-                                        .unwrap(),
-                                )
+                                if to.contains(BASENAME_VARIABLE) {
+                                    let basename = path.file_name().unwrap().to_string_lossy();
+                                    return PathBuf::from(to.replace(BASENAME_VARIABLE, &basename));
+                                }
+                                let relative = path.strip_prefix(from).unwrap();
+                                // `from` being a single file, which gets renamed to `to`,
+                                // unless `to` ends with a slash, which makes it a directory
+                                if relative == Path::new("") {
+                                    if to.ends_with('/') {
+                                        Path::new(&to).join(path.file_name().unwrap())
+                                    } else {
+                                        PathBuf::from(to)
+                                    }
+                                } else {
+                                    Path::new(&to).join(relative)
+                                }
                             })
                             .unwrap_or(path),
                         unpack,
                     ));
                 }
             }
-            if let Some((new_set, new_globs)) = self.sets.next() {
+            if let Some((new_set, new_globs, new_to)) = self.sets.next() {
+                self.globs = new_globs;
                 self.current_set = Some(new_set);
-                self.current_walk =
-                    WalkDir::new(self.root.join(new_set.from().unwrap_or_default()))
-                        .follow_links(true)
-                        .into_iter();
-                let mut filters = new_globs;
-                if !filters.iter().any(|f| !f.starts_with('!')) {
-                    let mut new_filters = vec!["**/*".to_string()];
-                    new_filters.extend(filters);
-                    filters = new_filters;
-                }
-                self.globs =
-                    Globreeks::new(filters.into_iter().by_ref().collect::<Vec<_>>()).unwrap();
+                self.current_to = new_to;
+                let from = new_set.from().unwrap_or_default();
+                let start = self.root.join(expand_home(from));
+                self.walk_root = if from.starts_with("~/") {
+                    start.clone()
+                } else {
+                    self.root.clone()
+                };
+                self.current_walk = WalkDir::new(start)
+                    .follow_links(self.follow_links)
+                    .into_iter();
             } else {
                 return None;
             }
@@ -133,8 +278,9 @@ impl<'a> Iterator for Walker<'a> {
 
 #[cfg(test)]
 mod tests {
-    use super::Walker;
+    use super::{anchored_glob, export_ignore_globs, with_descendants, Walker};
     use crate::app::App;
+    use crate::config::CopyDef;
     use crate::environment::{Platform, HOST_ENVIRONMENT};
     use anyhow::Result;
     use std::path::PathBuf;
@@ -153,6 +299,8 @@ mod tests {
                 .iter()
                 .collect::<Vec<_>>(),
             None,
+            Vec::new(),
+            true,
         )?;
 
         let full_list: Vec<_> = walker.collect();
@@ -167,4 +315,188 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_anchored_glob() {
+        assert_eq!(anchored_glob("/foo/bar.js".to_string()), "foo/bar.js");
+        assert_eq!(anchored_glob("!/foo".to_string()), "!foo");
+        assert_eq!(anchored_glob("foo/bar.js".to_string()), "**/foo/bar.js");
+        assert_eq!(anchored_glob("!*.md".to_string()), "!**/*.md");
+        assert_eq!(anchored_glob("**/*.md".to_string()), "**/*.md");
+    }
+
+    #[test]
+    fn test_with_descendants() {
+        assert_eq!(
+            with_descendants("node_modules/sharp"),
+            ["node_modules/sharp", "node_modules/sharp/**"]
+        );
+        assert_eq!(with_descendants("dir/"), ["dir", "dir/**"]);
+        assert_eq!(with_descendants("!*.node"), ["!*.node", "!*.node/**"]);
+    }
+
+    #[test]
+    fn test_export_ignore_globs() -> Result<()> {
+        let root = std::env::current_dir()?.join(".test-workspace/walker_export_ignore");
+        std::fs::create_dir_all(&root)?;
+        std::fs::write(
+            root.join(".gitattributes"),
+            "# comment\n*.png binary\n/tests export-ignore\ndocs/internal/ export-ignore\n*.psd -export-ignore\n.github export-ignore linguist-vendored\n",
+        )?;
+        assert_eq!(
+            export_ignore_globs(&root)?,
+            [
+                "!/tests",
+                "!/tests/**",
+                "!/docs/internal",
+                "!/docs/internal/**",
+                "!.github",
+                "!.github/**",
+            ]
+        );
+        assert!(export_ignore_globs(&root.join("nonexistent"))?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_walking_anchored() -> Result<()> {
+        let root = PathBuf::from("test_assets/packages/anchoring");
+        for (glob, expected) in [
+            ("/foo/bar.js", vec!["foo/bar.js"]),
+            ("foo/bar.js", vec!["foo/bar.js", "nested/foo/bar.js"]),
+        ] {
+            let files = [CopyDef::Simple(glob.to_string())];
+            let mut dests = Walker::new(
+                root.clone(),
+                HOST_ENVIRONMENT,
+                files.iter().collect(),
+                None,
+                Vec::new(),
+                true,
+            )?
+            .map(|(_, dest, _)| dest.to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+            dests.sort();
+            assert_eq!(dests, expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_walking_nested_to() -> Result<()> {
+        let root = std::env::current_dir()?.join(".test-workspace/walker_nested_to");
+        std::fs::create_dir_all(root.join("a/x/y"))?;
+        for file in ["a/top.js", "a/x/mid.js", "a/x/y/deep.js", "single.txt"] {
+            std::fs::write(root.join(file), file)?;
+        }
+        for (set, expected) in [
+            (
+                serde_json::json!({ "from": "a", "to": "b" }),
+                vec!["b/top.js", "b/x/mid.js", "b/x/y/deep.js"],
+            ),
+            (
+                serde_json::json!({ "from": "./a/", "to": "b/" }),
+                vec!["b/top.js", "b/x/mid.js", "b/x/y/deep.js"],
+            ),
+            (
+                serde_json::json!({ "from": "a/x//", "to": "./b" }),
+                vec!["b/mid.js", "b/y/deep.js"],
+            ),
+            (
+                serde_json::json!({ "from": "single.txt", "to": "renamed.txt" }),
+                vec!["renamed.txt"],
+            ),
+            (
+                serde_json::json!({ "from": "single.txt", "to": "dir/" }),
+                vec!["dir/single.txt"],
+            ),
+        ] {
+            let files: Vec<CopyDef> = serde_json::from_value(serde_json::json!([set]))?;
+            let mut dests = Walker::new(
+                root.clone(),
+                HOST_ENVIRONMENT,
+                files.iter().collect(),
+                None,
+                Vec::new(),
+                true,
+            )?
+            .map(|(_, dest, _)| dest.to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+            dests.sort();
+            assert_eq!(dests, expected, "{set}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_walking_invalid_set_filter() -> Result<()> {
+        let files: Vec<CopyDef> = serde_json::from_value(serde_json::json!([{
+            "from": "build",
+            "filter": ["**/*.js", "[unclosed"],
+        }]))?;
+        let err = Walker::new(
+            PathBuf::from("test_assets"),
+            HOST_ENVIRONMENT,
+            files.iter().collect(),
+            None,
+            Vec::new(),
+            true,
+        )
+        .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("invalid filter in the file set from \"build\""),
+            "{err:#}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_walking_basename() -> Result<()> {
+        let root = std::env::current_dir()?.join(".test-workspace/walker_basename");
+        for dir in ["src/a", "src/b/c"] {
+            std::fs::create_dir_all(root.join(dir))?;
+        }
+        for file in ["src/a/x.bin", "src/b/c/y.bin", "src/b/z.txt"] {
+            std::fs::write(root.join(file), file)?;
+        }
+        let files: Vec<CopyDef> = serde_json::from_value(serde_json::json!([{
+            "from": "src",
+            "to": "bin/${basename}",
+            "filter": ["**/*.bin"],
+        }]))?;
+        let mut dests = Walker::new(
+            root,
+            HOST_ENVIRONMENT,
+            files.iter().collect(),
+            None,
+            Vec::new(),
+            true,
+        )?
+        .map(|(source, dest, _)| {
+            (
+                source
+                    .file_name()
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+                dest.to_str().unwrap().to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+        dests.sort();
+        assert_eq!(
+            dests,
+            [
+                ("x.bin".to_string(), "bin/x.bin".to_string()),
+                ("y.bin".to_string(), "bin/y.bin".to_string()),
+            ]
+        );
+
+        Ok(())
+    }
 }
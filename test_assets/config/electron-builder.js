@@ -0,0 +1,6 @@
+module.exports = {
+    productName: "Tasje",
+    directories: {
+        output: "dist_out",
+    },
+};
@@ -0,0 +1 @@
+../desktop/main.js
\ No newline at end of file